@@ -0,0 +1,518 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use crate::spectrogram::{is_silent, StftResult, DB_RANGE};
+
+/// Level below the averaged spectrum's peak at which content is considered absent
+const CUTOFF_RELATIVE_DB: f32 = -70.0;
+
+/// Cutoffs below this (Hz) on CD-rate material point to lossy encoding
+const LOSSY_CUTOFF_HZ: f32 = 19_500.0;
+
+/// Sample rates above this are considered "hi-res"
+const HI_RES_SAMPLE_RATE: u32 = 48_000;
+
+/// Highest cutoff (Hz) a CD-rate master could have, plus some margin
+const CD_BANDWIDTH_HZ: f32 = 24_000.0;
+
+/// Highest harmonic order looked for above the fundamental
+const MAX_HARMONIC_ORDER: usize = 10;
+
+/// Bins on either side of the expected harmonic frequency searched for its peak
+const HARMONIC_SEARCH_BINS: usize = 2;
+
+/// A tonal peak must be the loudest bin within this many bins on each side
+const PEAK_NEIGHBOURHOOD_BINS: usize = 3;
+
+/// Bins on each side whose median is the local noise floor a tonal peak is measured against
+const PEAK_FLOOR_BINS: usize = 32;
+
+/// How far (dB) a tonal peak must rise above the local noise floor
+const PEAK_PROMINENCE_DB: f32 = 10.0;
+
+/// Oversampling factor of the true-peak estimator (as in ITU-R BS.1770)
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// Input samples per interpolation phase of the true-peak filter
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// Stereo correlation below which the mix is flagged as not mono compatible
+const MONO_INCOMPATIBLE_CORRELATION: f32 = -0.5;
+
+/// Integrated loudness gating block length (BS.1770)
+const LOUDNESS_BLOCK_SECS: f64 = 0.4;
+
+/// Absolute loudness gate (LUFS)
+const LOUDNESS_ABSOLUTE_GATE: f64 = -70.0;
+
+/// Relative loudness gate, below the absolute-gated level (LU)
+const LOUDNESS_RELATIVE_GATE: f64 = 10.0;
+
+/// Consecutive time segments a fingerprint is taken over
+const FINGERPRINT_SEGMENTS: usize = 4;
+
+/// Log-spaced bands per segment; neighbouring bands are compared, giving one bit per pair
+const FINGERPRINT_BANDS: usize = 17;
+
+/// Span (Hz) the fingerprint bands cover, kept below typical lossy cutoffs so a re-encode
+/// of the same master hashes alike
+const FINGERPRINT_MIN_HZ: f32 = 60.0;
+pub const FINGERPRINT_MAX_HZ: f32 = 12_000.0;
+
+/// Floor for peak levels so silence reports a finite dB value
+const MIN_PEAK_DB: f32 = -180.0;
+
+/// Overall quality assessment derived from the averaged spectrum
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    LikelyLossless,
+    LikelyLossy,
+    PossibleUpsampled,
+    Silent,
+}
+
+impl Verdict {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Verdict::LikelyLossless => "likely lossless",
+            Verdict::LikelyLossy => "likely lossy (transcoded)",
+            Verdict::PossibleUpsampled => "possible upsampled / fake hi-res",
+            Verdict::Silent => "silent",
+        }
+    }
+}
+
+/// Results of analyzing the time-averaged spectrum
+#[derive(Debug, Clone)]
+pub struct SpectrumAnalysis {
+    /// Mean magnitude per frequency bin over all non-silent frames
+    pub average_spectrum: Vec<f32>,
+    /// Highest frequency with meaningful content (Hz)
+    pub cutoff_hz: Option<f32>,
+    pub verdict: Verdict,
+}
+
+/// Harmonic structure of a single-tone test signal
+#[derive(Debug, Clone, Serialize)]
+pub struct HarmonicAnalysis {
+    /// Frequency of the loudest bin (Hz)
+    pub fundamental_hz: f32,
+    /// Harmonics 2f, 3f, ... below Nyquist
+    pub harmonics: Vec<Harmonic>,
+    /// Combined harmonic level relative to the fundamental, in percent (a rough THD)
+    pub thd_percent: f32,
+}
+
+/// Highest frequency with energy above a level relative to the peak
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MaxFreqEnergy {
+    /// Threshold relative to the averaged spectrum's peak (dB)
+    pub threshold_db: f32,
+    pub freq_hz: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Harmonic {
+    pub order: usize,
+    pub freq_hz: f32,
+    /// Level relative to the fundamental (dB)
+    pub level_db: f32,
+}
+
+/// A persistent tonal component of the averaged spectrum
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TonalPeak {
+    /// Frequency refined between bins by parabolic interpolation (Hz)
+    pub freq_hz: f32,
+    /// Level as the amplitude of an equivalent sine (dBFS)
+    pub level_db: f32,
+}
+
+/// Sample and intersample peak of the decoded signal
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PeakLevels {
+    pub sample_peak_dbfs: f32,
+    /// Peak of the 4x oversampled signal (dBTP)
+    pub true_peak_dbtp: f32,
+}
+
+impl PeakLevels {
+    /// Whether the reconstructed waveform goes above full scale
+    pub fn is_over(&self) -> bool {
+        self.true_peak_dbtp > 0.0
+    }
+}
+
+/// Measure the sample peak and estimate the true peak by 4x oversampling with a
+/// Hann-windowed sinc interpolator, evaluated as one short FIR per fractional phase
+pub fn measure_peaks(samples: &[f32]) -> PeakLevels {
+    let half = TRUE_PEAK_TAPS / 2;
+    // phases[p][k] weights samples[n + k + 1 - half] to interpolate at n + p / 4
+    let phases: Vec<Vec<f32>> = (1..TRUE_PEAK_OVERSAMPLING)
+        .map(|p| {
+            let t = p as f32 / TRUE_PEAK_OVERSAMPLING as f32;
+            let taps: Vec<f32> = (0..TRUE_PEAK_TAPS)
+                .map(|k| {
+                    let x = t - (k as f32 + 1.0 - half as f32);
+                    let sinc = (std::f32::consts::PI * x).sin() / (std::f32::consts::PI * x);
+                    let window = 0.5 * (1.0 + (std::f32::consts::PI * x / (half as f32 + 1.0)).cos());
+                    sinc * window
+                })
+                .collect();
+            // Unity gain at DC so a constant signal isn't reported as an over
+            let sum: f32 = taps.iter().sum();
+            taps.into_iter().map(|c| c / sum).collect()
+        })
+        .collect();
+
+    let sample_peak = samples.par_iter().fold(|| 0.0f32, |max, s| max.max(s.abs())).reduce(|| 0.0, f32::max);
+    let intersample_peak = samples.par_windows(TRUE_PEAK_TAPS)
+        .map(|window| {
+            phases.iter()
+                .map(|taps| taps.iter().zip(window).map(|(c, s)| c * s).sum::<f32>().abs())
+                .fold(0.0f32, f32::max)
+        })
+        .reduce(|| 0.0, f32::max);
+
+    let to_db = |level: f32| (20.0 * level.log10()).max(MIN_PEAK_DB);
+    PeakLevels {
+        sample_peak_dbfs: to_db(sample_peak),
+        true_peak_dbtp: to_db(sample_peak.max(intersample_peak)),
+    }
+}
+
+/// How the two channels of a stereo file relate
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StereoImage {
+    /// Pearson correlation of left and right: 1 for mono, 0 for unrelated channels,
+    /// -1 for one channel inverted against the other
+    pub correlation: f32,
+    /// Share of the energy in the side signal (L - R) rather than the mid (L + R):
+    /// 0 for mono, 0.5 for unrelated channels, 1 for fully out of phase
+    pub out_of_phase: f32,
+}
+
+impl StereoImage {
+    /// Whether summing to mono cancels a large part of the mix
+    pub fn is_mono_incompatible(&self) -> bool {
+        self.correlation < MONO_INCOMPATIBLE_CORRELATION
+    }
+}
+
+/// Measure the correlation and out-of-phase energy of a stereo pair.
+/// None if either channel is digital silence, where correlation is undefined.
+pub fn measure_stereo(left: &[f32], right: &[f32]) -> Option<StereoImage> {
+    let (mut ll, mut rr, mut lr) = (0.0f64, 0.0f64, 0.0f64);
+    for (&l, &r) in left.iter().zip(right) {
+        let (l, r) = (l as f64, r as f64);
+        ll += l * l;
+        rr += r * r;
+        lr += l * r;
+    }
+    if ll == 0.0 || rr == 0.0 {
+        return None;
+    }
+
+    // Mid and side energies follow from the same sums: (L ± R)² = L² + R² ± 2LR
+    let mid = ll + rr + 2.0 * lr;
+    let side = ll + rr - 2.0 * lr;
+    Some(StereoImage {
+        correlation: (lr / (ll * rr).sqrt()) as f32,
+        out_of_phase: (side / (mid + side)) as f32,
+    })
+}
+
+/// Integrated loudness (LUFS) per ITU-R BS.1770: K-weighted mean square over 400 ms blocks
+/// (75% overlap), gated at -70 LUFS and then 10 LU below the ungated level. Every channel is
+/// weighted 1.0, which is exact for mono and stereo. None if the signal is shorter than one
+/// block or gated out entirely.
+pub fn integrated_loudness(channels: &[Vec<f32>], sample_rate: u32) -> Option<f32> {
+    let block = (LOUDNESS_BLOCK_SECS * sample_rate as f64).round() as usize;
+    let step = block / 4;
+    let len = channels.iter().map(Vec::len).min().unwrap_or(0);
+    if block == 0 || len < block {
+        return None;
+    }
+
+    // Running sums of the K-weighted power, over all channels
+    let mut cumulative = vec![0.0f64; len + 1];
+    for channel in channels {
+        let mut filters = k_weighting(sample_rate);
+        let mut sum = 0.0f64;
+        for (i, &sample) in channel[..len].iter().enumerate() {
+            let filtered = filters.iter_mut().fold(sample as f64, |x, f| f.process(x));
+            sum += filtered * filtered;
+            cumulative[i + 1] += sum;
+        }
+    }
+
+    let block_power: Vec<f64> = (0..=(len - block) / step)
+        .map(|b| (cumulative[b * step + block] - cumulative[b * step]) / block as f64)
+        .collect();
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let gated_mean = |threshold: f64| {
+        let gated: Vec<f64> = block_power.iter().copied().filter(|&p| loudness(p) > threshold).collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+
+    let ungated = gated_mean(LOUDNESS_ABSOLUTE_GATE)?;
+    let relative_gate = (loudness(ungated) - LOUDNESS_RELATIVE_GATE).max(LOUDNESS_ABSOLUTE_GATE);
+    gated_mean(relative_gate).map(|power| loudness(power) as f32)
+}
+
+/// Direct form I biquad (a0 normalized to 1)
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad { b, a, x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The BS.1770 K-weighting filter (high shelf, then the RLB high-pass), designed for any
+/// sample rate from the analog prototype as in libebur128
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+/// Average the STFT magnitudes over time, skipping silent frames.
+/// The result always covers every bin up to Nyquist (`fft_bins`); bins above those kept in
+/// the STFT read as silence.
+pub fn average_spectrum(stft: &StftResult, silence_db: f32) -> Vec<f32> {
+    let mut sum = vec![0.0f32; stft.fft_bins];
+    let mut count = 0usize;
+
+    for frame in stft.magnitudes.iter().filter(|f| !is_silent(f, stft.fft_bins, silence_db)) {
+        for (acc, &mag) in sum.iter_mut().zip(frame.iter()) {
+            *acc += mag;
+        }
+        count += 1;
+    }
+
+    if count > 0 {
+        sum.iter_mut().for_each(|v| *v /= count as f32);
+    }
+    sum
+}
+
+/// Estimate the frequency above which the averaged spectrum contains no real content
+pub fn detect_cutoff(average_spectrum: &[f32], sample_rate: u32) -> Option<f32> {
+    highest_frequency_above(average_spectrum, sample_rate, CUTOFF_RELATIVE_DB)
+}
+
+/// Highest frequency (Hz, bin center) at which the averaged spectrum exceeds `relative_db` below its peak
+pub fn highest_frequency_above(average_spectrum: &[f32], sample_rate: u32, relative_db: f32) -> Option<f32> {
+    let peak = average_spectrum.iter().fold(0.0f32, |max, &v| max.max(v));
+    if peak <= 0.0 {
+        return None;
+    }
+
+    let threshold = peak * 10f32.powf(relative_db / 20.0);
+    let nyquist = sample_rate as f32 / 2.0;
+
+    average_spectrum.iter()
+        .rposition(|&mag| mag > threshold)
+        .map(|bin| bin as f32 * nyquist / average_spectrum.len() as f32)
+}
+
+/// Find the fundamental (loudest non-DC bin) and measure its harmonics in the averaged spectrum.
+/// Each harmonic is the peak within a couple of bins of its expected position.
+pub fn detect_harmonics(average_spectrum: &[f32], sample_rate: u32) -> Option<HarmonicAnalysis> {
+    let num_bins = average_spectrum.len();
+    let bin_hz = sample_rate as f32 / 2.0 / num_bins as f32;
+
+    let (fundamental_bin, &fundamental_mag) = average_spectrum.iter()
+        .enumerate()
+        .skip(1)
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    if fundamental_mag <= 0.0 {
+        return None;
+    }
+
+    // Parabolic interpolation around the peak so harmonic positions don't drift with order
+    let fundamental = match (average_spectrum.get(fundamental_bin - 1), average_spectrum.get(fundamental_bin + 1)) {
+        (Some(&left), Some(&right)) => {
+            let denom = left - 2.0 * fundamental_mag + right;
+            let offset = if denom.abs() > f32::EPSILON { 0.5 * (left - right) / denom } else { 0.0 };
+            fundamental_bin as f32 + offset.clamp(-0.5, 0.5)
+        }
+        _ => fundamental_bin as f32,
+    };
+
+    let harmonics: Vec<Harmonic> = (2..=MAX_HARMONIC_ORDER)
+        .map(|order| (order, (fundamental * order as f32).round() as usize))
+        .take_while(|&(_, bin)| bin + HARMONIC_SEARCH_BINS < num_bins)
+        .map(|(order, bin)| {
+            let (peak_bin, peak_mag) = (bin.saturating_sub(HARMONIC_SEARCH_BINS)..=bin + HARMONIC_SEARCH_BINS)
+                .map(|b| (b, average_spectrum[b]))
+                .fold((bin, 0.0f32), |best, cur| if cur.1 > best.1 { cur } else { best });
+            Harmonic {
+                order,
+                freq_hz: peak_bin as f32 * bin_hz,
+                level_db: 20.0 * (peak_mag / fundamental_mag + 1e-9).log10(),
+            }
+        })
+        .collect();
+
+    let harmonic_power: f32 = harmonics.iter()
+        .map(|h| 10f32.powf(h.level_db / 10.0))
+        .sum();
+
+    Some(HarmonicAnalysis {
+        fundamental_hz: fundamental * bin_hz,
+        harmonics,
+        thd_percent: harmonic_power.sqrt() * 100.0,
+    })
+}
+
+/// Find the `count` loudest tonal peaks in the averaged spectrum, loudest first.
+/// A peak is the loudest bin within a few bins on each side and stands at least
+/// `PEAK_PROMINENCE_DB` above the median of the surrounding bins, so broadband
+/// content and window sidelobes aren't reported. Peaks more than `DB_RANGE` below
+/// the loudest bin (off the color scale) are ignored.
+pub fn detect_tonal_peaks(average_spectrum: &[f32], sample_rate: u32, count: usize) -> Vec<TonalPeak> {
+    let num_bins = average_spectrum.len();
+    let bin_hz = sample_rate as f32 / 2.0 / num_bins as f32;
+    let prominence = 10f32.powf(PEAK_PROMINENCE_DB / 20.0);
+    let loudest = average_spectrum.iter().fold(0.0f32, |max, &v| max.max(v));
+    let min_mag = loudest * 10f32.powf(-DB_RANGE / 20.0);
+
+    let mut peaks: Vec<(usize, f32)> = (1..num_bins.saturating_sub(1))
+        .filter(|&bin| {
+            let mag = average_spectrum[bin];
+            let lo = bin.saturating_sub(PEAK_NEIGHBOURHOOD_BINS);
+            let hi = (bin + PEAK_NEIGHBOURHOOD_BINS + 1).min(num_bins);
+            mag > min_mag && average_spectrum[lo..hi].iter().all(|&other| other <= mag)
+                // Ties on a flat top: keep only the first bin
+                && average_spectrum[lo..bin].iter().all(|&other| other < mag)
+        })
+        .filter(|&bin| {
+            let lo = bin.saturating_sub(PEAK_FLOOR_BINS);
+            let hi = (bin + PEAK_FLOOR_BINS + 1).min(num_bins);
+            let mut floor: Vec<f32> = average_spectrum[lo..hi].to_vec();
+            floor.sort_by(|a, b| a.total_cmp(b));
+            average_spectrum[bin] > floor[floor.len() / 2] * prominence
+        })
+        .map(|bin| (bin, average_spectrum[bin]))
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    // Magnitude of a full-scale sine after the Hann window
+    let full_scale = num_bins as f32 / 2.0;
+    peaks.into_iter()
+        .take(count)
+        .map(|(bin, mag)| {
+            let (left, right) = (average_spectrum[bin - 1], average_spectrum[bin + 1]);
+            let denom = left - 2.0 * mag + right;
+            let offset = if denom.abs() > f32::EPSILON { 0.5 * (left - right) / denom } else { 0.0 };
+            TonalPeak {
+                freq_hz: (bin as f32 + offset.clamp(-0.5, 0.5)) * bin_hz,
+                level_db: 20.0 * (mag / full_scale + 1e-9).log10(),
+            }
+        })
+        .collect()
+}
+
+/// Classify the file from its detected cutoff relative to the sample rate
+pub fn classify(cutoff_hz: Option<f32>, sample_rate: u32) -> Verdict {
+    let cutoff = match cutoff_hz {
+        Some(c) => c,
+        None => return Verdict::Silent,
+    };
+
+    if sample_rate > HI_RES_SAMPLE_RATE && cutoff < CD_BANDWIDTH_HZ {
+        // A large empty region above ~22 kHz in a "hi-res" file
+        Verdict::PossibleUpsampled
+    } else if cutoff < LOSSY_CUTOFF_HZ {
+        Verdict::LikelyLossy
+    } else {
+        Verdict::LikelyLossless
+    }
+}
+
+/// Compact perceptual hash of the spectral content, for spotting the same audio across files.
+///
+/// The frames are split into `FINGERPRINT_SEGMENTS` spans of time and the power of each is
+/// summed into `FINGERPRINT_BANDS` log-spaced bands; each bit records whether a band is louder
+/// than the next one up. Only the shape of the spectrum counts, so gain changes, tags and the
+/// container don't alter the hash, and re-encodes differ in a few bits at most (compare
+/// fingerprints by the number of differing bits).
+///
+/// The hash does follow what the STFT was taken over: the span and channel analyzed, the
+/// direction of time, and the bins kept, which must reach `FINGERPRINT_MAX_HZ`.
+pub fn fingerprint(stft: &StftResult, sample_rate: u32) -> u64 {
+    let bin_hz = sample_rate as f32 / (2 * stft.fft_bins) as f32;
+    let ratio = (FINGERPRINT_MAX_HZ / FINGERPRINT_MIN_HZ).powf(1.0 / FINGERPRINT_BANDS as f32);
+    let to_bin = |f: f32| ((f / bin_hz).round() as usize).min(stft.num_freq_bins);
+    let bands: Vec<(usize, usize)> = (0..FINGERPRINT_BANDS)
+        .map(|i| {
+            let lo = to_bin(FINGERPRINT_MIN_HZ * ratio.powi(i as i32));
+            let hi = to_bin(FINGERPRINT_MIN_HZ * ratio.powi(i as i32 + 1));
+            // Narrow low bands still get a bin, unless it wasn't kept
+            (lo, hi.max(lo + 1).min(stft.num_freq_bins))
+        })
+        .collect();
+
+    let frames = stft.magnitudes.len();
+    let mut hash = 0u64;
+    for segment in 0..FINGERPRINT_SEGMENTS {
+        let span = &stft.magnitudes[segment * frames / FINGERPRINT_SEGMENTS..(segment + 1) * frames / FINGERPRINT_SEGMENTS];
+        let power: Vec<f64> = bands.iter()
+            .map(|&(lo, hi)| {
+                span.iter()
+                    .flat_map(|frame| frame.get(lo..hi).unwrap_or_default())
+                    .map(|&m| (m as f64).powi(2))
+                    .sum()
+            })
+            .collect();
+        for pair in power.windows(2) {
+            hash = (hash << 1) | (pair[0] > pair[1]) as u64;
+        }
+    }
+    hash
+}
+
+/// Run the averaged-spectrum analysis
+pub fn analyze_spectrum(stft: &StftResult, sample_rate: u32, silence_db: f32) -> SpectrumAnalysis {
+    let average_spectrum = average_spectrum(stft, silence_db);
+    let cutoff_hz = detect_cutoff(&average_spectrum, sample_rate);
+
+    SpectrumAnalysis {
+        average_spectrum,
+        cutoff_hz,
+        verdict: classify(cutoff_hz, sample_rate),
+    }
+}
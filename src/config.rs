@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use directories::ProjectDirs;
 use std::fs;
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
+use clap::ValueEnum;
+use std::sync::OnceLock;
 use crate::Palette;
 
 /// Main configuration struct
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Config {
     /// Default settings for CLI flags
     #[serde(default)]
@@ -18,6 +21,29 @@ pub struct Config {
     
     /// Path to custom font (optional)
     pub font_path: Option<PathBuf>,
+
+    /// Named output profiles (`[output.<name>]`), selected with `--profile`
+    #[serde(default)]
+    pub output: BTreeMap<String, OutputProfile>,
+}
+
+/// Output formatting settings applied together with `--profile <name>`.
+/// Keys left out fall back to `[defaults]`; command-line flags override both.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct OutputProfile {
+    /// Image format (extension) used when `--save` is given without one, e.g. "png"
+    pub format: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Built-in palette name
+    pub palette: Option<String>,
+    pub log_scale: Option<bool>,
+    /// Draw the color bar
+    pub legend: Option<bool>,
+    pub rolloff: Option<bool>,
+    pub octaves: Option<bool>,
+    /// Spectrogram only, no axes, legend or overlays
+    pub bare: Option<bool>,
 }
 
 /// Default values for CLI flags (can be overridden by command line)
@@ -46,11 +72,25 @@ pub struct DefaultSettings {
     /// Verbose mode by default
     #[serde(default)]
     pub verbose: bool,
+
+    /// Level (dBFS) below which a frame is considered silent
+    #[serde(default = "default_silence_db")]
+    pub silence_db: f32,
+
+    /// Largest accepted image width in pixels (guards against huge allocations)
+    #[serde(default = "default_max_dimension")]
+    pub max_width: u32,
+
+    /// Largest accepted image height in pixels
+    #[serde(default = "default_max_dimension")]
+    pub max_height: u32,
 }
 
 fn default_width() -> u32 { 2048 }
 fn default_height() -> u32 { 1024 }
 fn default_palette() -> String { "audacity".to_string() }
+fn default_silence_db() -> f32 { -60.0 }
+fn default_max_dimension() -> u32 { 16384 }
 
 impl Default for DefaultSettings {
     fn default() -> Self {
@@ -61,14 +101,30 @@ impl Default for DefaultSettings {
             palette: default_palette(),
             rolloff: false,
             verbose: false,
+            silence_db: default_silence_db(),
+            max_width: default_max_dimension(),
+            max_height: default_max_dimension(),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ColorConfig {
     #[serde(default = "default_color_stops")]
     pub stops: Vec<ColorStop>,
+
+    /// Whether the stops came from the config file rather than a built-in palette
+    #[serde(skip)]
+    pub custom: bool,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig {
+            stops: default_color_stops(),
+            custom: false,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -77,17 +133,94 @@ pub struct ColorStop {
     pub color: String, // Hex code "#RRGGBB"
 }
 
+impl ColorConfig {
+    /// Check that the stops form a usable gradient: at least two stops,
+    /// positions strictly increasing within 0.0..=1.0, and valid "#RRGGBB" colors
+    pub fn validate(&self) -> Result<()> {
+        if self.stops.len() < 2 {
+            bail!("colors.stops needs at least two stops (found {})", self.stops.len());
+        }
+
+        for (i, stop) in self.stops.iter().enumerate() {
+            if !(0.0..=1.0).contains(&stop.position) {
+                bail!("colors.stops[{}]: position {} is outside 0.0..=1.0", i, stop.position);
+            }
+            if i > 0 && stop.position <= self.stops[i - 1].position {
+                bail!(
+                    "colors.stops[{}]: position {} must be greater than the previous stop ({})",
+                    i, stop.position, self.stops[i - 1].position
+                );
+            }
+            let hex = stop.color.trim_start_matches('#');
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("colors.stops[{}]: color {:?} is not a \"#RRGGBB\" hex code", i, stop.color);
+            }
+        }
+
+        Ok(())
+    }
+}
+
 fn default_color_stops() -> Vec<ColorStop> {
     get_palette_stops_by_name("audacity")
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            defaults: DefaultSettings::default(),
-            colors: ColorConfig::default(),
-            font_path: None,
+/// Named color palettes: the built-ins, plus any registered at runtime.
+/// Names are case-insensitive; palettes are listed in the order they were registered.
+#[derive(Debug, Clone)]
+pub struct PaletteRegistry {
+    palettes: Vec<(String, Vec<ColorStop>)>,
+}
+
+impl PaletteRegistry {
+    /// A registry seeded with the built-in palettes, under their `--palette` names
+    pub fn new() -> Self {
+        let palettes = Palette::value_variants().iter()
+            .filter_map(|p| Some((p.to_possible_value()?.get_name().to_string(), get_palette_stops(*p))))
+            .collect();
+        Self { palettes }
+    }
+
+    /// The shared registry of built-in palettes
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<PaletteRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(Self::new)
+    }
+
+    /// Add a palette, replacing any existing one with the same name.
+    /// The stops are validated like a `[colors]` table.
+    pub fn register(&mut self, name: &str, stops: Vec<ColorStop>) -> Result<()> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            bail!("palette name must not be empty");
+        }
+        let colors = ColorConfig { stops, custom: true };
+        colors.validate()
+            .with_context(|| format!("Invalid palette {:?}", name))?;
+
+        match self.palettes.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, stops)) => *stops = colors.stops,
+            None => self.palettes.push((name, colors.stops)),
         }
+        Ok(())
+    }
+
+    /// Stops of the palette called `name`, if registered
+    pub fn get(&self, name: &str) -> Option<&[ColorStop]> {
+        let name = name.to_lowercase();
+        self.palettes.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, stops)| stops.as_slice())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.palettes.iter().map(|(n, _)| n.as_str())
+    }
+}
+
+impl Default for PaletteRegistry {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -102,16 +235,11 @@ pub fn get_palette_stops(palette: Palette) -> Vec<ColorStop> {
     }
 }
 
-/// Get color stops by palette name string
+/// Get color stops by built-in palette name, falling back to Audacity for unknown names
 pub fn get_palette_stops_by_name(name: &str) -> Vec<ColorStop> {
-    match name.to_lowercase().as_str() {
-        "audacity" => audacity_palette(),
-        "magma" => magma_palette(),
-        "viridis" => viridis_palette(),
-        "inferno" => inferno_palette(),
-        "grayscale" => grayscale_palette(),
-        _ => audacity_palette(),
-    }
+    PaletteRegistry::builtin().get(name)
+        .map(<[ColorStop]>::to_vec)
+        .unwrap_or_else(audacity_palette)
 }
 
 fn audacity_palette() -> Vec<ColorStop> {
@@ -162,6 +290,15 @@ fn grayscale_palette() -> Vec<ColorStop> {
     ]
 }
 
+/// Cyclic hue wheel for phase display: the same color at both ends, so -π and π match
+pub fn phase_wheel_stops() -> Vec<ColorStop> {
+    ["#FF0000", "#FFFF00", "#00FF00", "#00FFFF", "#0000FF", "#FF00FF", "#FF0000"]
+        .iter()
+        .enumerate()
+        .map(|(i, color)| ColorStop { position: i as f32 / 6.0, color: color.to_string() })
+        .collect()
+}
+
 /// Get the config directory path
 pub fn get_config_dir() -> Option<PathBuf> {
     ProjectDirs::from("", "", "spek").map(|p| p.config_dir().to_path_buf())
@@ -172,38 +309,138 @@ pub fn get_config_path() -> Option<PathBuf> {
     get_config_dir().map(|p| p.join("config.toml"))
 }
 
-/// Load config, creating default if it doesn't exist
-pub fn load_config() -> Result<Config> {
-    let config_path = match get_config_path() {
-        Some(p) => p,
-        None => return Ok(Config::default()),
-    };
-    
-    // Create config directory if it doesn't exist
+/// Name of the project-local config file searched for next to the audio file
+pub const LOCAL_CONFIG_NAME: &str = ".spek.toml";
+
+/// Load config, creating default if it doesn't exist.
+/// A `.spek.toml` found in the audio file's directory (or any parent) is merged
+/// over the global config. Also returns the dotted paths of any keys that were
+/// not recognized.
+pub fn load_config(audio_path: Option<&Path>) -> Result<(Config, Vec<String>)> {
+    let mut table = toml::Table::new();
+
+    if let Some(config_path) = get_config_path() {
+        // Create config directory if it doesn't exist
+        if let Some(parent) = config_path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+            }
+        }
+
+        ensure_config_file(&config_path)?;
+        table = read_config_table(&config_path)?;
+    }
+
+    if let Some(local_path) = audio_path.and_then(find_local_config) {
+        merge_tables(&mut table, read_config_table(&local_path)?);
+    }
+
+    config_from_value(toml::Value::Table(table))
+}
+
+/// Parse a complete config from TOML text without touching any config file.
+/// Also returns the dotted paths of any keys that were not recognized.
+pub fn parse_config(content: &str) -> Result<(Config, Vec<String>)> {
+    let table = content.parse::<toml::Table>()
+        .context("Failed to parse config")?;
+    config_from_value(toml::Value::Table(table))
+}
+
+/// Create the config directory and a commented default config if they don't exist yet
+fn ensure_config_file(config_path: &Path) -> Result<()> {
     if let Some(parent) = config_path.parent() {
         if !parent.exists() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
         }
     }
-    
-    // If config doesn't exist, create default one
+
     if !config_path.exists() {
-        create_default_config(&config_path)?;
+        create_default_config(config_path)?;
     }
-    
-    // Read and parse config
+    Ok(())
+}
+
+/// Write `[defaults]` values into the global config file, keeping its comments and layout.
+/// Returns the path of the updated file.
+pub fn save_defaults(values: &[(&str, toml::Value)]) -> Result<PathBuf> {
+    let config_path = get_config_path().context("Could not determine the config directory")?;
+    ensure_config_file(&config_path)?;
+
     let content = fs::read_to_string(&config_path)
         .with_context(|| format!("Failed to read config file: {:?}", config_path))?;
-    
-    let config: Config = toml::from_str(&content)
-        .with_context(|| "Failed to parse config file")?;
-    
-    Ok(config)
+    let mut document = content.parse::<toml_edit::DocumentMut>()
+        .with_context(|| format!("Failed to parse config file: {:?}", config_path))?;
+
+    let defaults = document.entry("defaults")
+        .or_insert(toml_edit::table())
+        .as_table_mut()
+        .context("[defaults] in the config file is not a table")?;
+    for (key, value) in values {
+        let value: toml_edit::Value = value.to_string().parse()
+            .with_context(|| format!("Failed to convert value for defaults.{}", key))?;
+        defaults[key] = toml_edit::Item::Value(value);
+    }
+
+    fs::write(&config_path, document.to_string())
+        .with_context(|| format!("Failed to write config file: {:?}", config_path))?;
+    Ok(config_path)
+}
+
+/// Search upward from the audio file's directory, then the current directory,
+/// for a project-local config file
+pub fn find_local_config(audio_path: &Path) -> Option<PathBuf> {
+    let audio_dir = audio_path.canonicalize().ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf));
+    let current_dir = std::env::current_dir().ok();
+
+    audio_dir.into_iter()
+        .chain(current_dir)
+        .flat_map(|dir| dir.ancestors().map(Path::to_path_buf).collect::<Vec<_>>())
+        .map(|dir| dir.join(LOCAL_CONFIG_NAME))
+        .find(|p| p.is_file())
+}
+
+fn read_config_table(path: &Path) -> Result<toml::Table> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {:?}", path))?;
+    content.parse::<toml::Table>()
+        .with_context(|| format!("Failed to parse config file: {:?}", path))
+}
+
+/// Recursively merge `overlay` into `base`; nested tables merge, other values replace
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Deserialize a merged config, collecting unrecognized keys instead of silently dropping them
+fn config_from_value(value: toml::Value) -> Result<(Config, Vec<String>)> {
+    let mut unknown_keys = Vec::new();
+    let custom_stops = value.get("colors").and_then(|colors| colors.get("stops")).is_some();
+    let mut config: Config = serde_ignored::deserialize(value, |path| {
+        unknown_keys.push(path.to_string());
+    })
+    .with_context(|| "Failed to parse config file")?;
+
+    config.colors.validate()
+        .context("Invalid [colors] table in config file")?;
+    config.colors.custom = custom_stops;
+
+    Ok((config, unknown_keys))
 }
 
 /// Create a default config file with helpful comments
-fn create_default_config(path: &PathBuf) -> Result<()> {
+fn create_default_config(path: &Path) -> Result<()> {
     let default_config = r##"# ╔═══════════════════════════════════════════════════════════════════════════╗
 # ║                        Spek-CLI Configuration                             ║
 # ╚═══════════════════════════════════════════════════════════════════════════╝
@@ -240,6 +477,15 @@ rolloff = false
 # Show timing statistics after processing
 verbose = false
 
+# Silence threshold in dBFS. Frames quieter than this are treated as silent
+# (e.g. the rolloff line is not drawn through them).
+silence_db = -60.0
+
+# Upper bounds for the image size. Larger --width/--height values are rejected
+# instead of trying to allocate gigabytes.
+max_width = 16384
+max_height = 16384
+
 # ─────────────────────────────────────────────────────────────────────────────
 # CUSTOM FONT (optional)
 # ─────────────────────────────────────────────────────────────────────────────
@@ -253,7 +499,8 @@ verbose = false
 # ─────────────────────────────────────────────────────────────────────────────
 # You can define your own color gradient here.
 # Each stop has a position (0.0 to 1.0) and a hex color.
-# Uncomment and modify to use a custom palette.
+# Uncomment and modify to use a custom palette. It replaces the default
+# palette above; --palette on the command line still picks a built-in one.
 #
 # [colors]
 # stops = [
@@ -264,6 +511,23 @@ verbose = false
 #     { position = 0.9, color = "#f9a03f" },  # Orange
 #     { position = 1.0, color = "#fcffc0" },  # Light yellow (max)
 # ]
+
+# ─────────────────────────────────────────────────────────────────────────────
+# OUTPUT PROFILES (optional)
+# ─────────────────────────────────────────────────────────────────────────────
+# Named sets of output settings, applied with --profile <name>. Keys you leave
+# out fall back to [defaults]; command-line flags still take priority.
+#
+# [output.archive]
+# format = "png"       # Used when --save has no extension
+# width = 4096
+# height = 1024
+# palette = "magma"
+# log_scale = true
+# legend = false
+# rolloff = false
+# octaves = false
+# bare = false
 "##;
     
     fs::write(path, default_config)
@@ -272,14 +536,7 @@ verbose = false
     Ok(())
 }
 
-/// Parse palette name to enum
+/// Parse palette name to enum (case-insensitive), falling back to the default palette
 pub fn parse_palette(name: &str) -> Palette {
-    match name.to_lowercase().as_str() {
-        "audacity" => Palette::Audacity,
-        "magma" => Palette::Magma,
-        "viridis" => Palette::Viridis,
-        "inferno" => Palette::Inferno,
-        "grayscale" => Palette::Grayscale,
-        _ => Palette::Audacity,
-    }
+    Palette::from_str(name, true).unwrap_or_default()
 }
@@ -1,15 +1,20 @@
-use anyhow::{anyhow, Result, Context};
-use symphonia::core::io::MediaSourceStream;
+use anyhow::{anyhow, bail, Result, Context};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::probe::Hint;
 use symphonia::core::codecs::{CodecType, DecoderOptions, CODEC_TYPE_NULL};
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::meta::MetadataOptions;
+use symphonia::core::formats::{FormatOptions, SeekMode, SeekTo};
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, Value};
 use symphonia::core::audio::{AudioBufferRef, Channels};
 use symphonia::core::conv::FromSample;
 use symphonia::core::audio::Signal;
+use symphonia::core::units::{Time, TimeBase, TimeStamp};
+use rayon::prelude::*;
+use serde::Serialize;
 use std::fs::File;
 use std::path::Path;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::analysis::{self, PeakLevels, StereoImage};
+use crate::progress::{self, ProgressCallback, Stage};
+use crate::GainMode;
 
 pub struct AudioData {
     pub samples: Vec<f32>,
@@ -17,47 +22,217 @@ pub struct AudioData {
     pub channels: u32,
     pub duration_secs: f64,
     pub metadata: AudioMetadata,
+    /// NaN/Inf samples that were replaced with silence
+    pub non_finite_samples: usize,
+    /// Packets that failed to decode and were skipped
+    pub corrupt_packets: usize,
+    /// Sample frames (per channel) those packets held, missing from `samples`
+    pub skipped_samples: u64,
+    /// ReplayGain adjustment applied to the samples, in dB
+    pub applied_gain_db: Option<f32>,
+    /// Correlation between the channels of a stereo source, measured before the downmix
+    pub stereo: Option<StereoImage>,
+    /// Integrated loudness of all source channels (LUFS), if measured and measurable.
+    /// `into_mono` leaves it unset; see `DecodedAudio::integrated_loudness`
+    pub loudness_lufs: Option<f32>,
+}
+
+/// Every channel of a decoded file, before any downmix
+pub struct DecodedAudio {
+    /// One sample vector per source channel, all the same length
+    pub channels: Vec<Vec<f32>>,
+    pub sample_rate: u32,
+    pub duration_secs: f64,
+    pub metadata: AudioMetadata,
+    /// NaN/Inf samples that were replaced with silence, across all channels
+    pub non_finite_samples: usize,
+    /// Packets that failed to decode and were skipped
+    pub corrupt_packets: usize,
+    /// Sample frames (per channel) those packets held, missing from `channels`
+    pub skipped_samples: u64,
+    /// ReplayGain adjustment applied to the samples, in dB
+    pub applied_gain_db: Option<f32>,
+}
+
+impl DecodedAudio {
+    /// Average all channels into one
+    pub fn downmix(&self) -> Vec<f32> {
+        let count = self.channels.len() as f32;
+        let frames = self.channels.first().map_or(0, Vec::len);
+        (0..frames)
+            .map(|i| self.channels.iter().map(|channel| channel[i]).sum::<f32>() / count)
+            .collect()
+    }
+
+    /// Highest sample and true peak over all source channels, so an over in one channel
+    /// isn't hidden by averaging it with the others
+    pub fn peaks(&self) -> PeakLevels {
+        self.channels.iter()
+            .map(|channel| analysis::measure_peaks(channel))
+            .reduce(|a, b| PeakLevels {
+                sample_peak_dbfs: a.sample_peak_dbfs.max(b.sample_peak_dbfs),
+                true_peak_dbtp: a.true_peak_dbtp.max(b.true_peak_dbtp),
+            })
+            .unwrap_or_else(|| analysis::measure_peaks(&[]))
+    }
+
+    /// Integrated loudness (LUFS) of all channels together, if measurable. This is a full
+    /// filtering pass, so it isn't part of `into_mono`
+    pub fn integrated_loudness(&self) -> Option<f32> {
+        analysis::integrated_loudness(&self.channels, self.sample_rate)
+    }
+
+    /// Reduce to the single analyzed signal: source channel `channel`, or the mono downmix
+    pub fn into_mono(mut self, channel: Option<usize>) -> Result<AudioData> {
+        if let Some(index) = channel.filter(|&index| index >= self.channels.len()) {
+            bail!("channel index {} out of range (file has {} channels)", index, self.channels.len());
+        }
+        let stereo = match &self.channels[..] {
+            [left, right] => analysis::measure_stereo(left, right),
+            _ => None,
+        };
+        let samples = match channel {
+            Some(index) => self.channels.swap_remove(index),
+            None => self.downmix(),
+        };
+
+        Ok(AudioData {
+            samples,
+            sample_rate: self.sample_rate,
+            channels: 1, // One selected channel or the mono downmix
+            duration_secs: self.duration_secs,
+            metadata: self.metadata,
+            non_finite_samples: self.non_finite_samples,
+            corrupt_packets: self.corrupt_packets,
+            skipped_samples: self.skipped_samples,
+            applied_gain_db: self.applied_gain_db,
+            stereo,
+            loudness_lufs: None,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AudioMetadata {
+    pub container: Option<String>,
     pub codec: String,
     pub bits_per_sample: Option<u32>,
     pub bit_rate: Option<u64>,
     pub channel_layout: String,
+    pub replay_gain: ReplayGain,
 }
 
-macro_rules! process_buffer {
-    ($buf:expr, $samples:expr) => {
-        for i in 0..$buf.frames() {
-            let mut sum = 0.0;
-            for c in 0..$buf.spec().channels.count() {
-                sum += f32::from_sample($buf.chan(c)[i]);
+/// ReplayGain values read from the file's tags
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplayGain {
+    pub track_gain_db: Option<f32>,
+    pub album_gain_db: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Gain to apply for `mode`; album mode falls back to the track gain like most players
+    pub fn gain_db(&self, mode: GainMode) -> Option<f32> {
+        match mode {
+            GainMode::Track => self.track_gain_db,
+            GainMode::Album => self.album_gain_db.or(self.track_gain_db),
+        }
+    }
+
+    /// Pick up any ReplayGain gain tags in a metadata revision
+    fn read_tags(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            match tag.std_key {
+                Some(StandardTagKey::ReplayGainTrackGain) => self.track_gain_db = parse_gain(&tag.value).or(self.track_gain_db),
+                Some(StandardTagKey::ReplayGainAlbumGain) => self.album_gain_db = parse_gain(&tag.value).or(self.album_gain_db),
+                _ => {}
             }
-            $samples.push(sum / $buf.spec().channels.count() as f32);
+        }
+    }
+}
+
+/// Options controlling how a file is decoded
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// Start of the span to decode, in seconds
+    pub start_secs: Option<f64>,
+    /// End of the span to decode, in seconds (exclusive)
+    pub end_secs: Option<f64>,
+    /// Analyze this source channel only instead of the mono downmix (`decode_file` only)
+    pub channel: Option<usize>,
+    /// Scale the samples by the file's ReplayGain value, if it has one
+    pub apply_gain: Option<GainMode>,
+    /// Fail if any packet can't be decoded, instead of skipping it
+    pub strict: bool,
+}
+
+macro_rules! process_buffer {
+    ($buf:expr, $channels:expr) => {
+        for (c, samples) in $channels.iter_mut().enumerate() {
+            samples.extend($buf.chan(c).iter().map(|&s| f32::from_sample(s)));
         }
     };
 }
 
-pub fn decode_file(path: &Path, quiet: bool) -> Result<AudioData> {
-    let file = File::open(path).with_context(|| format!("failed to open audio file: {:?}", path))?;
-    let file_size = file.metadata()?.len();
-    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+/// Decode `path` to a single signal: the channel chosen in `options`, or the mono downmix
+pub fn decode_file(
+    path: &Path,
+    options: &DecodeOptions,
+    progress: Option<&ProgressCallback>,
+) -> Result<AudioData> {
+    decode_with_options(path, options, progress)?.into_mono(options.channel)
+}
+
+/// Decode every channel of `path` with default options
+pub fn decode(path: &Path) -> Result<DecodedAudio> {
+    decode_with_options(path, &DecodeOptions::default(), None)
+}
 
-    let hint = Hint::new();
+/// Decode every channel of `path`; `options.channel` is only validated here, the
+/// selection is left to `DecodedAudio::into_mono`
+pub fn decode_with_options(
+    path: &Path,
+    options: &DecodeOptions,
+    progress: Option<&ProgressCallback>,
+) -> Result<DecodedAudio> {
+    let (source, file_size) = open_source(path)?;
+    let mss = MediaSourceStream::new(source, Default::default());
+
+    // Ignore any query string when taking the extension of a URL
+    let name = path.to_str().map(|p| p.split(['?', '#']).next().unwrap_or(p)).map(Path::new).unwrap_or(path);
+    let extension = name.extension().and_then(|e| e.to_str());
+    let mut hint = Hint::new();
+    if let Some(ext) = extension {
+        hint.with_extension(ext);
+    }
     let format_opts: FormatOptions = Default::default();
     let metadata_opts: MetadataOptions = Default::default();
     let decoder_opts: DecoderOptions = Default::default();
 
-    let probed = symphonia::default::get_probe()
+    let mut probed = symphonia::default::get_probe()
         .format(&hint, mss, &format_opts, &metadata_opts)
         .context("unsupported format")?;
 
     let mut format = probed.format;
+    let container = extension.and_then(container_to_string);
+
+    // Tags may sit before the container (e.g. ID3v2) or inside it (e.g. Vorbis comments)
+    let mut replay_gain = ReplayGain::default();
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        replay_gain.read_tags(revision);
+    }
+    if let Some(revision) = format.metadata().current() {
+        replay_gain.read_tags(revision);
+    }
+
+    // Video containers also carry video/subtitle tracks; pick the first decodable audio track
     let track = format.tracks()
         .iter()
-        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-        .ok_or_else(|| anyhow!("no supported audio tracks found"))?;
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL && t.codec_params.sample_rate.is_some())
+        .or_else(|| format.tracks().iter().find(|t| t.codec_params.codec != CODEC_TYPE_NULL))
+        .ok_or_else(|| match &container {
+            Some(name) => anyhow!("no supported audio track found in {} container", name),
+            None => anyhow!("no supported audio tracks found"),
+        })?;
 
     // Extract metadata
     let codec_name = codec_to_string(track.codec_params.codec);
@@ -66,43 +241,66 @@ pub fn decode_file(path: &Path, quiet: bool) -> Result<AudioData> {
     let channel_layout = channels_to_string(track.codec_params.channels);
 
     let metadata = AudioMetadata {
+        container,
         codec: codec_name,
         bits_per_sample,
         bit_rate,
         channel_layout,
+        replay_gain,
     };
 
     let mut decoder = symphonia::default::get_codecs()
         .make(&track.codec_params, &decoder_opts)
         .context("unsupported codec")?;
 
+    if let (Some(index), Some(channels)) = (options.channel, track.codec_params.channels) {
+        if index >= channels.count() {
+            return Err(anyhow!(
+                "channel index {} out of range (file has {} channels)",
+                index, channels.count()
+            ));
+        }
+    }
+
     let track_id = track.id;
+    let track_channels = track.codec_params.channels.map(|c| c.count());
     let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
-    let mut samples: Vec<f32> = Vec::new();
-
-    // Setup progress bar (only if not quiet)
-    let pb = if quiet {
-        ProgressBar::hidden()
-    } else {
-        let pb = ProgressBar::new(file_size);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} {msg} [{bar:40.cyan/blue}] {percent}% ({eta})")
-                .unwrap()
-                .progress_chars("━━╸")
-        );
-        pb.set_message("Decoding");
-        pb
-    };
+    let time_base = track.codec_params.time_base;
+    let mut channels: Vec<Vec<f32>> = Vec::new();
+    let mut packet_channels: Vec<Vec<f32>> = Vec::new();
+
+    // Requested span in frames (mono samples)
+    let start_frame = options.start_secs
+        .map(|s| (s.max(0.0) * sample_rate as f64) as u64)
+        .unwrap_or(0);
+    let end_frame = options.end_secs.map(|s| (s.max(0.0) * sample_rate as f64) as u64);
+
+    // Jump close to the requested start using the container's seek index.
+    // If the format can't seek we fall back to decoding from the beginning and trimming.
+    let mut position = 0u64;
+    if let Some(start) = options.start_secs.filter(|&s| s > 0.0) {
+        let seek_to = SeekTo::Time { time: Time::from(start), track_id: Some(track_id) };
+        if let Ok(seeked) = format.seek(SeekMode::Coarse, seek_to) {
+            decoder.reset();
+            position = ts_to_frame(seeked.actual_ts, time_base, sample_rate);
+        }
+    }
 
     let mut bytes_read = 0u64;
+    let mut corrupt_packets = 0usize;
+    let mut skipped_samples = 0u64;
 
     // Decode all packets
     loop {
+        progress::check_cancelled()?;
+
         let packet = match format.next_packet() {
             Ok(packet) => {
                 bytes_read += packet.buf().len() as u64;
-                pb.set_position(bytes_read.min(file_size));
+                // Streams without a known length only get the final report
+                if file_size > 0 {
+                    progress::report(progress, Stage::Decode, bytes_read as f32 / file_size as f32);
+                }
                 packet
             },
             Err(symphonia::core::errors::Error::IoError(err)) => {
@@ -121,39 +319,252 @@ pub fn decode_file(path: &Path, quiet: bool) -> Result<AudioData> {
             continue;
         }
 
+        if end_frame.is_some_and(|end| position >= end) {
+            break;
+        }
+
+        // Prefer the packet's own timestamp so trimming stays accurate after a seek
+        if time_base.is_some() {
+            position = ts_to_frame(packet.ts(), time_base, sample_rate);
+        }
+
         match decoder.decode(&packet) {
             Ok(decoded) => {
+                let channel_count = decoded.spec().channels.count();
+                if options.channel.is_some_and(|index| index >= channel_count) {
+                    return Err(anyhow!(
+                        "channel index {} out of range (file has {} channels)",
+                        options.channel.unwrap_or_default(), channel_count
+                    ));
+                }
+
+                let frames = decoded.frames();
+                packet_channels.resize_with(channel_count, Vec::new);
+                packet_channels.iter_mut().for_each(Vec::clear);
                 match decoded {
-                    AudioBufferRef::F32(buf) => process_buffer!(buf, samples),
-                    AudioBufferRef::U8(buf) => process_buffer!(buf, samples),
-                    AudioBufferRef::S16(buf) => process_buffer!(buf, samples),
-                    AudioBufferRef::S24(buf) => process_buffer!(buf, samples),
-                    AudioBufferRef::S32(buf) => process_buffer!(buf, samples),
+                    AudioBufferRef::F32(buf) => process_buffer!(buf, packet_channels),
+                    AudioBufferRef::U8(buf) => process_buffer!(buf, packet_channels),
+                    AudioBufferRef::S16(buf) => process_buffer!(buf, packet_channels),
+                    AudioBufferRef::S24(buf) => process_buffer!(buf, packet_channels),
+                    AudioBufferRef::S32(buf) => process_buffer!(buf, packet_channels),
                     _ => return Err(anyhow!("unsupported sample format")),
                 }
+
+                // A channel that appears mid-stream starts with silence to stay aligned
+                let decoded_frames = channels.first().map_or(0, Vec::len);
+                if channels.len() < channel_count {
+                    channels.resize(channel_count, vec![0.0; decoded_frames]);
+                }
+
+                // Keep only the part of this packet that falls inside the requested span
+                let packet_start = position;
+                let packet_end = packet_start + frames as u64;
+                let keep_start = start_frame.clamp(packet_start, packet_end);
+                let keep_end = end_frame.unwrap_or(u64::MAX).clamp(keep_start, packet_end);
+                let keep = (keep_start - packet_start) as usize..(keep_end - packet_start) as usize;
+                for (c, samples) in channels.iter_mut().enumerate() {
+                    match packet_channels.get(c) {
+                        Some(packet_samples) => samples.extend_from_slice(&packet_samples[keep.clone()]),
+                        // ...and one that disappears is padded the same way
+                        None => samples.resize(samples.len() + keep.len(), 0.0),
+                    }
+                }
+                position = packet_end;
             }
             Err(symphonia::core::errors::Error::DecodeError(_)) => {
+                corrupt_packets += 1;
+                skipped_samples += ts_to_frame(packet.dur(), time_base, sample_rate);
                 continue;
             }
             Err(err) => return Err(anyhow::Error::new(err)),
         }
     }
 
-    if !quiet {
-        pb.finish_with_message("Decoded ✓");
+    progress::report(progress, Stage::Decode, 1.0);
+
+    // Strict mode still decodes to the end so the error can say how much is damaged
+    if options.strict && corrupt_packets > 0 {
+        bail!("{} packet{} failed to decode (strict mode)", corrupt_packets, if corrupt_packets == 1 { "" } else { "s" });
+    }
+
+    // Malformed float files can contain NaN/Inf, which would poison the FFT and normalization
+    let mut non_finite_samples = 0;
+    for sample in channels.iter_mut().flatten().filter(|s| !s.is_finite()) {
+        *sample = 0.0;
+        non_finite_samples += 1;
+    }
+
+    let applied_gain_db = options.apply_gain.and_then(|mode| replay_gain.gain_db(mode));
+    if let Some(gain_db) = applied_gain_db {
+        let factor = 10f32.powf(gain_db / 20.0);
+        channels.par_iter_mut().flat_map(|c| c.par_iter_mut()).for_each(|s| *s *= factor);
     }
 
-    let duration_secs = samples.len() as f64 / sample_rate as f64;
+    // A file with no decodable packets still has its declared channels, empty
+    if channels.is_empty() {
+        channels.resize(track_channels.unwrap_or(1), Vec::new());
+    }
+    let duration_secs = channels[0].len() as f64 / sample_rate as f64;
 
-    Ok(AudioData {
-        samples,
+    Ok(DecodedAudio {
+        channels,
         sample_rate,
-        channels: 1, // We mixed down to mono
         duration_secs,
         metadata,
+        non_finite_samples,
+        corrupt_packets,
+        skipped_samples,
+        applied_gain_db,
     })
 }
 
+/// Parse a gain tag such as "-6.48 dB"
+fn parse_gain(value: &Value) -> Option<f32> {
+    match value {
+        Value::Float(db) => Some(*db as f32),
+        Value::String(text) => text.trim()
+            .trim_end_matches("dB")
+            .trim_end_matches("db")
+            .trim()
+            .parse()
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Whether the input names an http(s) URL rather than a local file
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|p| p.starts_with("http://") || p.starts_with("https://"))
+}
+
+/// Open the input as a media source, returning it with its size in bytes (0 if unknown)
+fn open_source(path: &Path) -> Result<(Box<dyn MediaSource>, u64)> {
+    if is_url(path) {
+        return open_url(path.to_str().unwrap_or_default());
+    }
+
+    let file = File::open(path).with_context(|| format!("failed to open audio file: {:?}", path))?;
+    let file_size = file.metadata()?.len();
+    Ok((Box::new(file), file_size))
+}
+
+/// Stream a remote file; the response body is read as it arrives and can't seek,
+/// so `--start` decodes from the beginning and trims
+#[cfg(feature = "http")]
+fn open_url(url: &str) -> Result<(Box<dyn MediaSource>, u64)> {
+    use symphonia::core::io::ReadOnlySource;
+
+    let response = ureq::get(url)
+        .call()
+        .with_context(|| format!("failed to fetch {}", url))?;
+    let size = response.header("Content-Length")
+        .and_then(|len| len.parse().ok())
+        .unwrap_or(0);
+    Ok((Box::new(ReadOnlySource::new(response.into_reader())), size))
+}
+
+#[cfg(not(feature = "http"))]
+fn open_url(url: &str) -> Result<(Box<dyn MediaSource>, u64)> {
+    Err(anyhow!("cannot open {}: URL inputs need a build with the `http` feature", url))
+}
+
+/// A container format the built-in probe can detect
+#[derive(Debug, Serialize)]
+pub struct FormatInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub extensions: &'static [&'static str],
+    pub mime_types: &'static [&'static str],
+}
+
+/// A codec the built-in decoders can decode
+#[derive(Debug, Serialize)]
+pub struct CodecInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupportedFormats {
+    pub containers: Vec<FormatInfo>,
+    pub codecs: Vec<CodecInfo>,
+}
+
+/// Containers and codecs this build can handle, read from the readers and decoders
+/// Symphonia registers by default. Codecs are kept only if `get_codecs()` knows them.
+pub fn supported_formats() -> SupportedFormats {
+    use symphonia::core::codecs::Decoder;
+    use symphonia::core::probe::{Descriptor, QueryDescriptor};
+    use symphonia::default::{codecs, formats, get_codecs};
+
+    let readers: [&[Descriptor]; 9] = [
+        formats::AdtsReader::query(),
+        formats::AiffReader::query(),
+        formats::CafReader::query(),
+        formats::FlacReader::query(),
+        formats::IsoMp4Reader::query(),
+        formats::MkvReader::query(),
+        formats::MpaReader::query(),
+        formats::OggReader::query(),
+        formats::WavReader::query(),
+    ];
+    let containers = readers.iter()
+        .flat_map(|descriptors| descriptors.iter())
+        .map(|d| FormatInfo {
+            name: d.short_name,
+            description: d.long_name.trim(),
+            extensions: d.extensions,
+            mime_types: d.mime_types,
+        })
+        .collect();
+
+    let registry = get_codecs();
+    let decoders = [
+        codecs::AacDecoder::supported_codecs(),
+        codecs::AdpcmDecoder::supported_codecs(),
+        codecs::AlacDecoder::supported_codecs(),
+        codecs::FlacDecoder::supported_codecs(),
+        codecs::MpaDecoder::supported_codecs(),
+        codecs::PcmDecoder::supported_codecs(),
+        codecs::VorbisDecoder::supported_codecs(),
+    ];
+    let codecs = decoders.iter()
+        .flat_map(|descriptors| descriptors.iter())
+        .filter(|d| registry.get_codec(d.codec).is_some())
+        .map(|d| CodecInfo { name: d.short_name, description: d.long_name.trim() })
+        .collect();
+
+    SupportedFormats { containers, codecs }
+}
+
+/// Convert a track timestamp to a frame index at `sample_rate`
+fn ts_to_frame(ts: TimeStamp, time_base: Option<TimeBase>, sample_rate: u32) -> u64 {
+    match time_base {
+        Some(tb) => {
+            let time = tb.calc_time(ts);
+            ((time.seconds as f64 + time.frac) * sample_rate as f64).round() as u64
+        }
+        None => ts,
+    }
+}
+
+fn container_to_string(extension: &str) -> Option<String> {
+    let name = match extension.to_lowercase().as_str() {
+        "mp4" | "m4a" | "m4b" | "m4v" | "mov" => "MP4",
+        "mkv" | "mka" => "Matroska",
+        "webm" => "WebM",
+        "ogg" | "oga" | "opus" => "Ogg",
+        "wav" | "wave" => "WAV",
+        "aif" | "aiff" | "aifc" => "AIFF",
+        "caf" => "CAF",
+        "flac" => "FLAC",
+        "mp3" => "MP3",
+        "aac" => "ADTS",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
 fn codec_to_string(codec: CodecType) -> String {
     // Check against known codec types
     use symphonia::core::codecs::*;
@@ -0,0 +1,147 @@
+//! Spek-CLI audio spectrum analysis: decoding, STFT, quality analysis and rendering.
+//!
+//! The pipeline has no randomness: the same samples and options always produce the
+//! same spectrum, statistics and image, so results can be reproduced exactly.
+
+pub mod analysis;
+pub mod config;
+pub mod decoder;
+pub mod palette_file;
+pub mod spectrogram;
+pub mod progress;
+pub mod reference;
+pub mod render;
+pub mod report;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum Palette {
+    #[default]
+    Audacity,
+    Magma,
+    Viridis,
+    Inferno,
+    Grayscale,
+}
+
+/// How magnitudes are mapped onto the color scale
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum AmplitudeScale {
+    /// Decibels relative to the peak (100 dB range)
+    #[default]
+    Db,
+    /// Magnitude normalized linearly to the peak (0..1)
+    Linear,
+}
+
+/// Curve applied to the color scale position before it picks a palette color
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum ColorCurve {
+    /// Palette spread evenly over the scale
+    #[default]
+    Linear,
+    /// Square root: expands the low and middle levels
+    Sqrt,
+    /// Logarithmic: expands the low levels strongly
+    Log,
+}
+
+/// Unit used for frequency axis labels
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum FreqUnit {
+    /// Hz below 1 kHz, kHz above (e.g. "500", "2k")
+    #[default]
+    Auto,
+    /// Always Hz (e.g. "500", "2000")
+    Hz,
+    /// Always kHz (e.g. "0.5k", "2k")
+    Khz,
+}
+
+/// What the top of the color scale is anchored to
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum Normalization {
+    /// The loudest bin in the whole file
+    #[default]
+    Peak,
+    /// The 99th percentile of per-frame peaks, so a single click or transient doesn't dim the plot
+    Robust,
+    /// A fixed level, `--ref-db` (0 dBFS by default), so quiet and loud files keep their difference
+    Absolute,
+}
+
+/// How bins are weighted when accumulating energy for the spectral rolloff
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum RolloffMetric {
+    /// Squared magnitudes (energy), as in Audacity and librosa
+    #[default]
+    Power,
+    /// Plain magnitudes; puts the rolloff at a higher frequency
+    Magnitude,
+}
+
+/// How analysis frames are mapped onto image columns
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum TimeMapping {
+    /// Interpolate between the two nearest frames
+    #[default]
+    Interpolate,
+    /// When several frames fall into one column, take the loudest value of each bin,
+    /// so short transients survive overview renders of long files
+    Max,
+}
+
+/// Which ReplayGain value to apply before analysis
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum GainMode {
+    /// Per-track gain
+    #[default]
+    Track,
+    /// Per-album gain, falling back to the track gain if the file has none
+    Album,
+}
+
+/// Which rolloff value is printed next to the rolloff line
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum RolloffLabel {
+    /// Median over all non-silent frames
+    #[default]
+    Median,
+    /// Value of the last non-silent frame
+    Final,
+    /// No label
+    Off,
+}
+
+/// How the image is sized when shown in the terminal
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum TerminalFit {
+    /// Scale to the largest size that fits the terminal, keeping the aspect ratio
+    #[default]
+    Fit,
+    /// Fill the whole terminal, distorting the aspect ratio if needed
+    Stretch,
+}
+
+/// Bits per color channel of saved images
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum BitDepth {
+    /// 8 bits per channel
+    #[default]
+    #[value(name = "8")]
+    Eight,
+    /// 16 bits per channel: smooth gradients without banding (PNG and TIFF only)
+    #[value(name = "16")]
+    Sixteen,
+}
+
+/// What each spectrogram pixel shows
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum SpectrogramMode {
+    /// Magnitude mapped through the palette
+    #[default]
+    Magnitude,
+    /// Phase of each bin on a cyclic hue wheel, with brightness from the magnitude
+    Phase,
+}
@@ -1,38 +1,105 @@
-pub mod config;
-pub mod decoder;
-pub mod spectrogram;
-pub mod render;
-
-
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
-use std::time::Instant;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::{IsTerminal, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
 use anyhow::{Result, Context};
 use viuer::Config as ViuerConfig;
-use crossterm::terminal::size;
+use crossterm::cursor::{MoveTo, Show};
+use crossterm::style::ResetColor;
+use crossterm::terminal::{size, Clear, ClearType};
 use owo_colors::OwoColorize;
+use image::ImageFormat;
+use spek_cli::{
+    analysis, config, decoder, palette_file, progress, reference, render, report, spectrogram,
+    AmplitudeScale, BitDepth, ColorCurve, FreqUnit, GainMode, Normalization, Palette, RolloffLabel, RolloffMetric, SpectrogramMode, TerminalFit,
+    TimeMapping,
+};
 
-#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
-pub enum Palette {
-    #[default]
-    Audacity,
-    Magma,
-    Viridis,
-    Inferno,
-    Grayscale,
-}
-
+/// Command line: a subcommand, or the options of `analyze` directly (`spek-cli file.flac`)
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Audio Spectrum Analyzer - Check audio quality from your terminal", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Analyze files and show the spectrogram in the terminal, or save it with --save (the default)
+    Analyze(Args),
+    /// Print codec, rate, duration, cutoff and verdict without rendering (same as --check)
+    Info(Args),
+    /// Render two or more files against one shared color scale (same as --compare-normalization)
+    Compare(Args),
+    /// Render and save the spectrogram; --save is required
+    Render(Args),
+}
+
+impl Cli {
+    /// The options for the chosen subcommand, with the settings it implies
+    fn into_args(self) -> Result<Args> {
+        match self.command {
+            None => {
+                // `spek-cli -w 800 render a.flac` parses `render` as a file: options go after it
+                let misplaced = self.args.files.first()
+                    .filter(|file| !file.exists())
+                    .and_then(|file| file.to_str())
+                    .filter(|name| Command::has_subcommand(name));
+                if let Some(name) = misplaced {
+                    anyhow::bail!("options go after the subcommand: spek-cli {} [OPTIONS] <FILE>...", name);
+                }
+                Ok(self.args)
+            }
+            Some(Command::Analyze(args)) => Ok(args),
+            Some(Command::Info(mut args)) => {
+                if args.save.is_some() || args.watch {
+                    anyhow::bail!("info doesn't render an image; use render or analyze with --save / --watch");
+                }
+                args.check = true;
+                Ok(args)
+            }
+            Some(Command::Compare(mut args)) => {
+                if args.files.len() < 2 {
+                    anyhow::bail!("compare needs at least two files, got {}", args.files.len());
+                }
+                if args.check {
+                    anyhow::bail!("compare renders images; use info to check several files");
+                }
+                // Loudness normalization already puts every file on one absolute scale
+                args.compare_normalization = args.loudness_normalize.is_none();
+                Ok(args)
+            }
+            Some(Command::Render(args)) => {
+                if args.save.is_none() {
+                    anyhow::bail!("render needs --save (-s) with the image path or directory");
+                }
+                Ok(args)
+            }
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
 struct Args {
-    /// Path to the audio file
-    #[arg(required = true)]
-    file: PathBuf,
+    /// Path to the audio file (or an http(s) URL in builds with the `http` feature).
+    /// Several files are analyzed one after another; --save then names an output directory
+    #[arg(value_name = "FILE", required_unless_present_any = ["list_palettes", "formats", "palette_preview", "save_defaults"])]
+    files: Vec<PathBuf>,
 
     /// Width of the output image in pixels
     #[arg(short, long)]
     width: Option<u32>,
 
+    /// Derive the width from the duration: N pixels per second of audio
+    #[arg(long, value_name = "N", conflicts_with = "width", value_parser = parse_positive)]
+    pps: Option<f64>,
+
     /// Height of the output image in pixels
     #[arg(short = 'H', long)]
     height: Option<u32>,
@@ -45,182 +112,1944 @@ struct Args {
     #[arg(short = 'p', long, value_enum)]
     palette: Option<Palette>,
 
+    /// Load the color palette from a file: .toml (stops array), GIMP .ggr, or .csv (position,#RRGGBB)
+    #[arg(long, value_name = "FILE", conflicts_with = "palette")]
+    palette_file: Option<PathBuf>,
+
+    /// Paint pixels at or below the bottom of the color scale in this color (#RRGGBB),
+    /// to tell silence and the floor apart from quiet content
+    #[arg(long, value_name = "HEX", value_parser = parse_hex_color, conflicts_with = "barcode")]
+    floor_color: Option<image::Rgb<u8>>,
+
+    /// Dither the color mapping to hide banding in smooth gradients of the 8-bit image
+    #[arg(long, conflicts_with = "barcode")]
+    dither: bool,
+
+    /// Start of the time range to analyze, in seconds
+    #[arg(long, value_name = "SECS")]
+    start: Option<f64>,
+
+    /// End of the time range to analyze, in seconds
+    #[arg(long, value_name = "SECS")]
+    end: Option<f64>,
+
+    /// Analyze a single source channel (0-based) instead of the mono downmix.
+    /// Avoids cancellation of out-of-phase content.
+    #[arg(long, value_name = "N")]
+    channel_index: Option<usize>,
+
+    /// Apply the file's ReplayGain (track by default, or --apply-gain=album) before analysis.
+    /// Shifts absolute levels such as --silence-db, --cutoff-line and reported dBFS values
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "track")]
+    apply_gain: Option<GainMode>,
+
+    /// Color mapping of magnitudes: decibels or linear amplitude
+    #[arg(long, value_enum, default_value_t = AmplitudeScale::Db)]
+    amplitude: AmplitudeScale,
+
+    /// Curve applied to the color scale before picking palette colors; sqrt and log spread the
+    /// quieter levels over more of the palette. The color bar labels follow the curve
+    #[arg(long, value_enum, value_name = "CURVE", default_value_t = ColorCurve::Linear)]
+    color_curve: ColorCurve,
+
+    /// Reference level for the color scale: this file's peak, a robust high percentile, or a
+    /// fixed absolute level (see --ref-db)
+    #[arg(long, value_enum, default_value_t = Normalization::Peak)]
+    normalize: Normalization,
+
+    /// Level (dBFS) at the top of the color scale; implies --normalize absolute (default 0 dBFS
+    /// there). The scale spans down to this minus the dB range, e.g. -20 to focus on quiet material
+    #[arg(long, value_name = "DBFS", allow_hyphen_values = true, value_parser = parse_ref_db)]
+    ref_db: Option<f32>,
+
+    /// With several files, measure them all first and render every one against the loudest
+    /// reference level, so images of an album can be compared
+    #[arg(long, conflicts_with = "ref_db")]
+    compare_normalization: bool,
+
+    /// Scale each file to this integrated loudness (default -14 LUFS) before analysis, so
+    /// tracks compare at matched perceived loudness. Implies --normalize absolute.
+    /// The target is written --loudness-normalize=-16, so a bare flag never takes the file path
+    #[arg(
+        long, value_name = "LUFS", num_args = 0..=1, require_equals = true, default_missing_value = "-14",
+        allow_negative_numbers = true, value_parser = parse_loudness_target,
+        conflicts_with = "compare_normalization",
+    )]
+    loudness_normalize: Option<f32>,
+
+    /// How frames map onto columns when the file has more frames than the image is wide:
+    /// interpolate two neighbours, or keep the per-bin maximum so transients aren't skipped
+    #[arg(long, value_enum, default_value_t = TimeMapping::Interpolate)]
+    time_mapping: TimeMapping,
+
+    /// On the log scale, draw frequencies below ~500Hz from a 4x longer FFT so bass notes are
+    /// resolved instead of smeared (at some cost in time resolution down there)
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    bass_detail: bool,
+
+    /// Sharpen the display by narrowing bright spectral ridges (strength, default 2).
+    /// Makes tones crisper; analysis results are unaffected
+    #[arg(long, value_name = "STRENGTH", num_args = 0..=1, default_missing_value = "2", value_parser = parse_positive_f32)]
+    sharpen: Option<f32>,
+
+    /// FFT size in samples (default 2048). Any even size works, e.g. 4410 for exact 10 Hz bins
+    /// at 44.1 kHz; frames advance by a quarter of it
+    #[arg(long, value_name = "N", default_value_t = spectrogram::WINDOW_SIZE, value_parser = parse_fft_size)]
+    fft_size: usize,
+
+    /// Zero-pad each windowed frame to FACTOR times the FFT size before transforming: finer,
+    /// interpolated bin spacing for cleaner tonal peaks, without a longer window's time smearing
+    #[arg(long, value_name = "FACTOR", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8))]
+    zero_pad: u8,
+
+    /// Keep only the FFT bins up to this frequency in Hz (default: Nyquist). Saves memory and
+    /// time on 96/192 kHz files; the display, cutoff and verdict then stop at this frequency
+    #[arg(long, value_name = "HZ", value_parser = parse_positive_f32)]
+    analysis_max_freq: Option<f32>,
+
+    /// Lowest FFT bins to zero before analysis and display: 0 keeps DC, 1 drops the DC bin,
+    /// 2 also drops the bin next to it (where a DC offset leaks through the window)
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=2))]
+    dc_bins: u8,
+
+    /// Silence threshold in dBFS; quieter frames are treated as silent
+    #[arg(long, value_name = "DBFS", allow_negative_numbers = true)]
+    silence_db: Option<f32>,
+
+    /// Skip the FFT of frames whose level is below DBFS (default -120) and draw them as silence;
+    /// speeds up recordings with long silent gaps
+    #[arg(long, value_name = "DBFS", num_args = 0..=1, default_missing_value = "-120", allow_negative_numbers = true)]
+    fft_gate: Option<f32>,
+
+    /// Show magnitude, or phase (hue from a cyclic wheel, brightness from magnitude) for
+    /// debugging filter ringing and phase issues; phase mode ignores --palette
+    #[arg(long, value_enum, default_value_t = SpectrogramMode::Magnitude)]
+    mode: SpectrogramMode,
+
+    /// Shade alternating octaves like a piano roll (log scale only)
+    #[arg(long, overrides_with = "no_octaves")]
+    octaves: bool,
+
+    /// Don't shade octaves, even if the --profile turns them on
+    #[arg(long, overrides_with = "octaves")]
+    no_octaves: bool,
+
+    /// Draw a line tracing the highest frequency above this level (dBFS) in each frame.
+    /// A simpler brick-wall indicator than the 85% rolloff.
+    #[arg(long, value_name = "DB", allow_negative_numbers = true)]
+    cutoff_line: Option<f32>,
+
+    /// Lowest frequency to show, in Hz; the slice is stretched to fill the image height
+    #[arg(long, value_name = "HZ", value_parser = parse_non_negative)]
+    freq_min: Option<f32>,
+
+    /// Highest frequency to show, in Hz (default: Nyquist)
+    #[arg(long, value_name = "HZ", value_parser = parse_non_negative)]
+    freq_max: Option<f32>,
+
+    /// Unit for frequency axis labels, applied the same way in linear and log scale
+    #[arg(long, value_enum, default_value_t = FreqUnit::Auto)]
+    freq_unit: FreqUnit,
+
+    /// Thickness of the dark outline around labels in pixels, up to 8 (0 disables it)
+    #[arg(long, value_name = "PX", default_value_t = 1, value_parser = clap::value_parser!(u32).range(0..=8))]
+    text_outline: u32,
+
+    /// Blank margin around the image in pixels: one value for every side, two for
+    /// vertical,horizontal, or four for top,right,bottom,left. Labels at the edges extend into
+    /// it, and the Hz, scale and Time labels move into top and bottom margins of 18px or more
+    #[arg(long, value_name = "PX", value_parser = parse_margins)]
+    margins: Option<render::Margins>,
+
+    /// Draw the color bar legend (default: only in saved images, not in the terminal)
+    #[arg(long)]
+    legend: Option<bool>,
+
+    /// Estimate the noise floor (a low percentile of each frame's median bin level, in dBFS per
+    /// FFT bin), show it in the metadata panel and JSON, and mark it on the color bar
+    #[arg(long)]
+    noise_floor: bool,
+
+    /// Number of evenly spaced labels on the color bar (top and bottom included)
+    #[arg(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u32).range(2..=51))]
+    legend_ticks: u32,
+
+    /// Render the time-averaged spectrum as a small horizontal color strip (frequency left to right)
+    /// instead of a spectrogram. Height defaults to 24px; --bare drops the frequency labels
+    #[arg(long)]
+    barcode: bool,
+
+    /// Also write the time-averaged spectrum as a line plot (frequency across, dBFS up) to PATH.
+    /// Uses the spectrogram's width, height and frequency scale
+    #[arg(long, value_name = "PATH")]
+    spectrum_plot: Option<PathBuf>,
+
+    /// Also write the per-column rolloff, centroid and peak frequency (unsmoothed, one row per
+    /// image column with its time) as CSV to PATH
+    #[arg(long, value_name = "PATH")]
+    series_csv: Option<PathBuf>,
+
+    /// Draw the rolloff and centroid lines from a CSV written by --series-csv (e.g. for an
+    /// earlier encoder build) over this spectrogram in red, for A/B comparison
+    #[arg(long, value_name = "CSV")]
+    overlay_series: Option<PathBuf>,
+
+    /// Output only the spectrogram (no axes, legend, or overlays) at exactly width x height
+    #[arg(long, overrides_with = "no_bare")]
+    bare: bool,
+
+    /// Keep axes, legend and overlays, even if the --profile sets bare output
+    #[arg(long, overrides_with = "bare")]
+    no_bare: bool,
+
+    /// Draw a vertical timecode grid every N seconds (also sets the time label interval)
+    #[arg(long, value_name = "SECS", value_parser = parse_positive)]
+    time_grid: Option<f64>,
+
+    /// Interval between time axis ticks in seconds (default: chosen from duration and width).
+    /// Ticks closer than 100px apart are labeled only every few ticks
+    #[arg(long, value_name = "SECS", value_parser = parse_positive)]
+    time_ticks: Option<f64>,
+
+    /// Reverse the audio in time before analysis, e.g. to compare a track against itself played
+    /// backwards. The time axis still runs from 0 to the duration
+    #[arg(long)]
+    reverse: bool,
+
+    /// Apply A-weighting to the spectrum to de-emphasize inaudible lows and extreme highs
+    #[arg(long)]
+    a_weight: bool,
+
+    /// Tilt the display by DB per octave around 1 kHz before color mapping (e.g. 3 for pink-noise
+    /// compensation) to lift dim high-frequency detail; analysis is unaffected
+    #[arg(long, value_name = "DB", allow_negative_numbers = true, value_parser = parse_tilt)]
+    tilt: Option<f32>,
+
+    /// Report the highest frequency whose averaged energy is within DB of the peak (default -90)
+    #[arg(long, value_name = "DB", num_args = 0..=1, default_missing_value = "-90", allow_negative_numbers = true)]
+    max_freq_energy: Option<f32>,
+
+    /// Detect the fundamental of a test tone, mark its harmonics and report a rough THD
+    #[arg(long)]
+    harmonics: bool,
+
+    /// Mark and list the N loudest persistent tonal peaks (frequency and level in dBFS)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=32))]
+    peaks: Option<u32>,
+
+    /// Print a 64-bit hash of the spectral content (hex) with the summary and in --json, to spot
+    /// the same audio across files regardless of tags or container. Compare hashes taken with
+    /// the same --start/--end, --channel-index and --reverse; needs --analysis-max-freq of 12kHz or more
+    #[arg(long)]
+    fingerprint: bool,
+
+    /// Validate the file: decode and analyze it, print codec, rate, duration, cutoff and verdict,
+    /// then exit without rendering (with --json, print those fields as JSON)
+    #[arg(long, conflicts_with_all = ["save", "watch"])]
+    check: bool,
+
+    /// Fail (exit nonzero) if any packet of the file can't be decoded, instead of skipping
+    /// corrupt packets and analyzing the rest
+    #[arg(long)]
+    strict: bool,
+
+    /// Keep running and re-analyze whenever the file changes (e.g. re-exported from a DAW)
+    #[arg(long)]
+    watch: bool,
+
+    /// Worker threads for the FFT, rolloff and rendering (0 = one per CPU core)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    threads: usize,
+
+    /// Run everything on one thread, for reproducible timings (same as --threads 1)
+    #[arg(long, conflicts_with = "threads")]
+    single_thread: bool,
+
     /// Quiet mode (suppress all progress output)
     #[arg(short = 'q', long)]
     quiet: bool,
 
-    /// Save spectrogram to PNG file instead of displaying in terminal
+    /// Don't print the File Information panel (progress and the image are kept)
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// Print the header and File Information as plain `key: value` lines without box drawing.
+    /// Chosen automatically when the terminal is narrower than the box
+    #[arg(long)]
+    compact: bool,
+
+    /// How the image is sized in the terminal: keep its aspect ratio, or stretch to fill
+    #[arg(long, value_enum, default_value_t = TerminalFit::Fit)]
+    fit: TerminalFit,
+
+    /// Apply the `[output.NAME]` profile from the config (format, size, palette, legend, overlays);
+    /// flags given on the command line still win
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Use this TOML as the whole config instead of the config files (`-` reads it from stdin),
+    /// e.g. in CI or containers where the config directory is read-only
+    #[arg(long, value_name = "TOML", conflicts_with = "save_defaults")]
+    config_inline: Option<String>,
+
+    /// Save spectrogram to an image file instead of displaying in terminal ("-" writes PNG to stdout)
     #[arg(short = 's', long)]
     save: Option<PathBuf>,
 
+    /// Bits per color channel of the saved image; 16 removes banding in smooth gradients
+    /// (PNG and TIFF only)
+    #[arg(long, value_enum, default_value_t = BitDepth::Eight, requires = "save", conflicts_with = "barcode")]
+    bit_depth: BitDepth,
+
+    /// Render the saved image at FACTOR (1, 2 or 4) times its size and downsample it, for
+    /// anti-aliased axes, lines and text. Memory and render time grow with FACTOR squared
+    #[arg(long, value_name = "FACTOR", default_value_t = 1, value_parser = parse_supersample, requires = "save", conflicts_with = "barcode")]
+    supersample: u32,
+
+    /// Write a GIF that scrolls a --gif-window long view across the file instead of a still image
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["save", "barcode", "check", "watch"])]
+    gif: Option<PathBuf>,
+
+    /// Seconds of audio visible in each GIF frame; the view scrolls in real time
+    #[arg(long, value_name = "SECS", default_value_t = 10.0, value_parser = parse_positive, requires = "gif")]
+    gif_window: f64,
+
+    /// GIF frames per second
+    #[arg(long, value_name = "FPS", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=50), requires = "gif")]
+    gif_fps: u32,
+
+    /// Also write a JSON analysis summary next to the saved image (out.png -> out.json)
+    #[arg(long, requires = "save")]
+    sidecar: bool,
+
+    /// Print the analysis summary as JSON to stdout (no terminal image)
+    #[arg(long)]
+    json: bool,
+
+    /// Process the files as a batch even if there is only one. With --json, print one compact
+    /// JSON object per file and line (JSON lines), failures included, with no progress output
+    #[arg(long)]
+    batch: bool,
+
+    /// Print a quality verdict (lossless / lossy / fake hi-res) based on the detected cutoff
+    #[arg(long)]
+    verdict: bool,
+
+    /// Compare the averaged spectrum against a reference curve (TOML of freq/dB points)
+    #[arg(long, value_name = "CURVE")]
+    reference: Option<PathBuf>,
+
     /// Show timing statistics after processing
     #[arg(short = 'v', long)]
     verbose: Option<bool>,
 
+    /// Store the options given on this command line (size, scale, palette, rolloff, verbosity,
+    /// silence threshold) as the new defaults in the global config file
+    #[arg(long)]
+    save_defaults: bool,
+
+    /// Render a palette as a gradient swatch image (with --save, or shown in the terminal) and exit.
+    /// Size defaults to 512x48; --bare drops the name caption
+    #[arg(long, value_enum, value_name = "PALETTE")]
+    palette_preview: Option<Palette>,
+
+    /// List the built-in color palettes with a preview and exit
+    #[arg(long)]
+    list_palettes: bool,
+
+    /// List the container formats and codecs this build can decode and exit (with --json, as JSON)
+    #[arg(long)]
+    formats: bool,
+
     /// Show spectral rolloff indicator line. The rolloff frequency is where
     /// 85% of the audio energy is concentrated. Useful for detecting lossy
     /// compression - MP3s typically show a steep rolloff around 16kHz.
     #[arg(long)]
     rolloff: Option<bool>,
 
+    /// Energy weighting for the rolloff: power (squared magnitude, default) or magnitude
+    #[arg(long, value_enum, default_value_t = RolloffMetric::Power)]
+    rolloff_metric: RolloffMetric,
+
+    /// Rolloff value printed at the right end of the rolloff line
+    #[arg(long, value_enum, default_value_t = RolloffLabel::Median)]
+    rolloff_label: RolloffLabel,
+
+    /// Smooth the rolloff line with a moving average over N analysis frames
+    #[arg(long, value_name = "FRAMES", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    rolloff_smooth: u32,
+
+    /// Show the spectral centroid (brightness) line: the energy-weighted mean frequency of each frame
+    #[arg(long)]
+    centroid: bool,
+
+    /// Smooth the centroid line with a moving average over N analysis frames
+    #[arg(long, value_name = "FRAMES", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    centroid_smooth: u32,
 
 }
 
+/// Strip height for --barcode when no --height is given
+const BARCODE_HEIGHT: u32 = 24;
+
+/// Warn about banding when one 8-bit color covers more than this fraction of a custom palette
+/// (about 8 rows of a 512-pixel-tall spectrogram)
+const BANDING_WARN_FRACTION: f32 = 1.0 / 64.0;
+
 fn main() -> Result<()> {
-    let args = Args::parse();
-    let total_start = Instant::now();
+    // First Ctrl-C stops the current stage cleanly; a second one exits immediately
+    ctrlc::set_handler(|| {
+        if progress::is_cancelled() {
+            std::process::exit(130);
+        }
+        progress::cancel();
+    })
+    .context("Failed to install Ctrl-C handler")?;
 
-    // Load config (creates default if doesn't exist)
-    let mut config = config::load_config().unwrap_or_else(|e| {
-        if !args.quiet {
-            eprintln!("{} {}", "".yellow(), format!("Config warning: {}. Using defaults.", e).dimmed());
+    match run() {
+        Err(e) if e.is::<progress::Cancelled>() => {
+            restore_terminal();
+            eprintln!("{}", "Cancelled".yellow());
+            std::process::exit(130);
+        }
+        result => result,
+    }
+}
+
+/// Undo terminal changes a half-finished progress bar or image may have left behind
+fn restore_terminal() {
+    let _ = crossterm::execute!(std::io::stderr(), ResetColor, Show);
+    eprintln!();
+}
+
+fn run() -> Result<()> {
+    let mut args = Cli::parse().into_args()?;
+    if args.ref_db.is_some() || args.loudness_normalize.is_some() {
+        args.normalize = Normalization::Absolute;
+    }
+    // Human-readable output (progress, metadata, warnings) goes to stderr;
+    // stdout is reserved for requested results (JSON, terminal image, verdict)
+    let quiet = args.quiet;
+
+    // Fingerprints are only comparable when taken over the same bands
+    if let Some(max_freq) = args.analysis_max_freq.filter(|&f| args.fingerprint && f < analysis::FINGERPRINT_MAX_HZ) {
+        anyhow::bail!(
+            "--fingerprint needs the bins up to {}Hz; --analysis-max-freq {} would drop some and change the hash",
+            analysis::FINGERPRINT_MAX_HZ, max_freq
+        );
+    }
+    if args.fingerprint && args.reverse && !quiet {
+        eprintln!("{} {}", "".yellow(), "--reverse changes the fingerprint; compare it only with other reversed runs".dimmed());
+    }
+
+    // The global pool is fixed by the first parallel iterator, so configure it before any work
+    let threads = if args.single_thread { 1 } else { args.threads };
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .context("Failed to configure the thread pool")?;
+
+    if args.config_inline.as_deref() == Some("-") {
+        let mut toml = String::new();
+        std::io::stdin().read_to_string(&mut toml)
+            .context("Failed to read --config-inline from stdin")?;
+        args.config_inline = Some(toml);
+    }
+
+    if args.list_palettes {
+        print_palettes();
+        return Ok(());
+    }
+    if args.formats {
+        return print_formats(args.json);
+    }
+    if let Some(palette) = args.palette_preview {
+        return palette_preview(&args, palette);
+    }
+
+    if args.save_defaults {
+        save_defaults(&args)?;
+        if args.files.is_empty() {
+            return Ok(());
+        }
+    }
+    if args.files.len() > 1 || args.batch {
+        return batch(&args);
+    }
+    let file = args.files.first().cloned().context("No audio file given")?;
+    let config = load_config(&args, &file, quiet);
+    apply_profile(&mut args, &config)?;
+
+    if args.watch && decoder::is_url(&file) {
+        anyhow::bail!("--watch needs a local file, not a URL");
+    }
+
+    if args.check {
+        check(&args, &file, &config)
+    } else if args.watch {
+        watch(&args, &file, &config)
+    } else {
+        analyze(&args, &file, config, None)
+    }
+}
+
+/// Load the config for `file` (creating the default if there is none), or the `--config-inline`
+/// one instead, reporting problems unless quiet
+fn load_config(args: &Args, file: &Path, quiet: bool) -> config::Config {
+    let loaded = match &args.config_inline {
+        Some(toml) => config::parse_config(toml).context("Invalid --config-inline"),
+        None => config::load_config(Some(file)),
+    };
+    let (config, unknown_keys) = loaded.unwrap_or_else(|e| {
+        if !quiet {
+            eprintln!("{} {}", "".yellow(), format!("Config warning: {:#}. Using defaults.", e).dimmed());
         }
-        config::Config::default()
+        (config::Config::default(), Vec::new())
     });
+    if !quiet {
+        for key in &unknown_keys {
+            eprintln!("{} {}", "".yellow(), format!("Config warning: unknown config key: {}", key).dimmed());
+        }
+    }
+    config
+}
+
+/// Analyze (or `--check`) several files in turn. A file that fails is reported and skipped;
+/// the run fails at the end if any did. With `--save`, images go into that directory as
+/// `<file stem>.png`. With `--compare-normalization`, a first pass finds the loudest
+/// reference level across all files so every image shares one color scale.
+/// With `--json --batch`, stdout is JSON lines: one summary per file, or `{"file", "error"}`.
+fn batch(args: &Args) -> Result<()> {
+    let json_lines = args.json && args.batch;
+    let mut args = args.clone();
+    // Only the JSON lines go out; nothing but warnings is left on stderr
+    args.quiet |= json_lines;
+    let args = &args;
+
+    for (flag, set) in [
+        ("--watch", args.watch),
+        ("--gif", args.gif.is_some()),
+        ("--spectrum-plot", args.spectrum_plot.is_some()),
+        ("--series-csv", args.series_csv.is_some()),
+        ("--save -", args.save.as_deref() == Some(Path::new("-"))),
+    ] {
+        if set {
+            anyhow::bail!("{} works on a single file, not a batch of {}", flag, args.files.len());
+        }
+    }
+
+    if let Some(ref dir) = args.save {
+        if dir.extension().is_some_and(|e| SAVE_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str())) {
+            anyhow::bail!("With several files, --save names an output directory, not an image ({})", dir.display());
+        }
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create output directory {:?}", dir))?;
+    }
+
+    // An absolute scale is already shared; there is nothing to measure
+    let reference_db = if args.compare_normalization && !args.check && args.normalize != Normalization::Absolute {
+        Some(shared_reference_db(args)?)
+    } else {
+        None
+    };
+
+    let mut failed = 0;
+    for file in &args.files {
+        let config = load_config(args, file, args.quiet);
+        let mut file_args = args.clone();
+        file_args.save = None;
+        let result = apply_profile(&mut file_args, &config).and_then(|()| {
+            // Name the image after the file; stems may contain dots, so append rather than replace
+            file_args.save = args.save.as_ref().map(|dir| {
+                let format = args.profile.as_ref()
+                    .and_then(|name| config.output.get(name))
+                    .and_then(|profile| profile.format.as_deref())
+                    .unwrap_or("png");
+                let stem = file.file_stem().unwrap_or(file.as_os_str()).to_string_lossy();
+                dir.join(format!("{}.{}", stem, format))
+            });
+            if args.check {
+                check(&file_args, file, &config)
+            } else {
+                analyze(&file_args, file, config, reference_db)
+            }
+        });
+        match result {
+            Err(e) if e.is::<progress::Cancelled>() => return Err(e),
+            Err(e) => {
+                if json_lines {
+                    let error = report::FileError { file: file.clone(), error: format!("{:#}", e) };
+                    println!("{}", report::to_json_line(&error)?);
+                } else {
+                    eprintln!("{} {}: {:#}", "Error:".red().bold(), file.display(), e);
+                }
+                failed += 1;
+            }
+            Ok(()) => {}
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} files failed", failed, args.files.len());
+    }
+    Ok(())
+}
+
+/// Fill in options not given on the command line from the `--profile` output profile
+fn apply_profile(args: &mut Args, config: &config::Config) -> Result<()> {
+    let Some(ref name) = args.profile else {
+        return Ok(());
+    };
+    let profile = config.output.get(name).with_context(|| {
+        let names: Vec<&str> = config.output.keys().map(String::as_str).collect();
+        if names.is_empty() {
+            format!("No [output.{}] profile in the config (none are defined)", name)
+        } else {
+            format!("No [output.{}] profile in the config (available: {})", name, names.join(", "))
+        }
+    })?;
+
+    if let Some(ref format) = profile.format {
+        if !SAVE_EXTENSIONS.contains(&format.to_lowercase().as_str()) {
+            anyhow::bail!("[output.{}]: cannot save images as {:?} (supported: {})", name, format, SAVE_EXTENSIONS.join(", "));
+        }
+        if let Some(ref save) = args.save {
+            if save.extension().is_none() && save.as_path() != Path::new("-") {
+                args.save = Some(save.with_file_name(format!("{}.{}", save.file_name().unwrap_or_default().to_string_lossy(), format)));
+            }
+        }
+    }
+    if args.pps.is_none() {
+        args.width = args.width.or(profile.width);
+    }
+    args.height = args.height.or(profile.height);
+    if let (None, None, Some(palette)) = (args.palette, &args.palette_file, &profile.palette) {
+        args.palette = Some(Palette::from_str(palette, true)
+            .map_err(|_| anyhow::anyhow!("[output.{}]: unknown palette {:?}", name, palette))?);
+    }
+    args.log = args.log.or(profile.log_scale);
+    args.legend = args.legend.or(profile.legend);
+    args.rolloff = args.rolloff.or(profile.rolloff);
+    // Plain flags, so --no-octaves / --no-bare are how the command line turns them off
+    if !args.octaves && !args.no_octaves {
+        args.octaves = profile.octaves.unwrap_or(false);
+    }
+    if !args.bare && !args.no_bare {
+        args.bare = profile.bare.unwrap_or(false);
+    }
+    Ok(())
+}
+
+/// First pass of `--compare-normalization`: the highest color scale reference over all files
+fn shared_reference_db(args: &Args) -> Result<f32> {
+    let mut shared: Option<f32> = None;
+    for (i, file) in args.files.iter().enumerate() {
+        progress::check_cancelled()?;
+        if !args.quiet {
+            eprintln!("{}", format!("Measuring levels ({}/{}): {}", i + 1, args.files.len(), file.display()).dimmed());
+        }
+
+        let config = load_config(args, file, true);
+        let options = spectrogram_options(args, &config, 0, 0, None);
+        let level = decoder::decode_file(file, &decode_options(args), None)
+            .context("Failed to decode audio file")
+            .and_then(|audio| spectrogram::reference_level_db(&audio.samples, audio.sample_rate, &options));
+        match level {
+            Ok(db) => shared = Some(shared.map_or(db, |s| s.max(db))),
+            // Reported again, and counted, when the file is rendered
+            Err(e) if !args.quiet => eprintln!("{} {}: {:#}", "".yellow(), file.display(), e),
+            Err(_) => {}
+        }
+    }
+
+    let shared = shared.context("No file in the batch could be decoded")?;
+    if !args.quiet {
+        eprintln!("{} {:.1} dBFS", "Shared reference level:".cyan(), shared);
+    }
+    Ok(shared)
+}
+
+/// Write the flags given on the command line into the config's `[defaults]` table
+fn save_defaults(args: &Args) -> Result<()> {
+    let values: Vec<(&str, toml::Value)> = [
+        ("width", args.width.map(|w| toml::Value::Integer(w.into()))),
+        ("height", args.height.map(|h| toml::Value::Integer(h.into()))),
+        ("log_scale", args.log.map(toml::Value::Boolean)),
+        ("palette", args.palette.map(|p| toml::Value::String(palette_name(p)))),
+        ("rolloff", args.rolloff.map(toml::Value::Boolean)),
+        ("verbose", args.verbose.map(toml::Value::Boolean)),
+        // Go through the shortest decimal form so -45.3 isn't stored as -45.29999923706055
+        ("silence_db", args.silence_db.map(|db| toml::Value::Float(db.to_string().parse().unwrap_or(db.into())))),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.map(|v| (key, v)))
+    .collect();
+
+    if values.is_empty() {
+        anyhow::bail!("--save-defaults: no storable options given (--width, --height, --log, --palette, --rolloff, --verbose, --silence-db)");
+    }
+
+    let path = config::save_defaults(&values)?;
+    if !args.quiet {
+        let keys: Vec<&str> = values.iter().map(|(key, _)| *key).collect();
+        eprintln!("{} Saved defaults ({}) to {}", "".green().bold(), keys.join(", "), path.display().to_string().cyan());
+    }
+    Ok(())
+}
+
+/// Re-run the analysis whenever the file's modification time changes.
+/// Waits for the mtime to settle first so a file that is still being written isn't read half-way.
+fn watch(args: &Args, file: &Path, config: &config::Config) -> Result<()> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let mut last_run = None;
+    loop {
+        progress::check_cancelled()?;
+
+        let current = modified(file);
+        if current.is_some() && current != last_run {
+            thread::sleep(POLL_INTERVAL);
+            if modified(file) != current {
+                continue;
+            }
+            last_run = current;
+
+            if std::io::stderr().is_terminal() {
+                crossterm::execute!(std::io::stderr(), Clear(ClearType::All), MoveTo(0, 0))?;
+            }
+            match analyze(args, file, config.clone(), None) {
+                Err(e) if e.is::<progress::Cancelled>() => return Err(e),
+                Err(e) => eprintln!("{} {:#}", "Error:".red().bold(), e),
+                Ok(()) => {}
+            }
+            if !args.quiet {
+                eprintln!();
+                eprintln!("{}", format!("Watching {} for changes (Ctrl-C to stop)", file.display()).dimmed());
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Decode and analyze a file without rendering, then print a one-line report (or JSON) to stdout
+fn check(args: &Args, file: &Path, config: &config::Config) -> Result<()> {
+    let silence_db = args.silence_db.unwrap_or(config.defaults.silence_db);
+
+    let terminal_progress = progress::TerminalProgress::new();
+    let report_progress = |stage, fraction| terminal_progress.update(stage, fraction);
+    let progress: Option<&progress::ProgressCallback> = if args.quiet { None } else { Some(&report_progress) };
+
+    let audio_data = decoder::decode_file(file, &decode_options(args), progress)
+        .context("Failed to decode audio file")?;
+    if !args.quiet {
+        warn_corrupt_packets(&audio_data);
+        warn_if_shorter_than_window(audio_data.samples.len(), args.fft_size);
+        warn_if_analysis_limited(args, audio_data.sample_rate);
+    }
+    let samples = spectrogram::pad_to_window(&audio_data.samples, args.fft_size);
+    let hop_size = spectrogram::hop_size(args.fft_size);
+    let zero_pad = usize::from(args.zero_pad);
+    let fft_size = args.fft_size * zero_pad;
+    let num_bins = spectrogram::bins_up_to(args.analysis_max_freq, audio_data.sample_rate, fft_size);
+    let mut stft = spectrogram::stft_lowest_bins(
+        &samples, args.fft_size, fft_size, hop_size, num_bins, args.fft_gate, progress,
+    )?;
+    spectrogram::remove_dc(&mut stft, usize::from(args.dc_bins) * zero_pad);
+    let spectrum = analysis::analyze_spectrum(&stft, audio_data.sample_rate, silence_db);
+    let fingerprint = args.fingerprint.then(|| analysis::fingerprint(&stft, audio_data.sample_rate));
+
+    if args.json {
+        let mut report = report::CheckReport::new(file, &audio_data, &spectrum);
+        report.fingerprint = fingerprint.map(report::fingerprint_hex);
+        println!("{}", if args.batch { report::to_json_line(&report)? } else { report.to_json()? });
+    } else {
+        println!("{} {}", file.display(), format_summary_line(&audio_data, &spectrum, None, fingerprint));
+    }
+    Ok(())
+}
+
+/// Corrupt packets are skipped, so their audio is missing from the analysis; say how much
+fn warn_corrupt_packets(audio_data: &decoder::AudioData) {
+    if audio_data.corrupt_packets > 0 {
+        eprintln!(
+            "{} {}",
+            "".yellow(),
+            format!(
+                "Warning: {} corrupt packet{} skipped ({:.2}s of audio missing)",
+                audio_data.corrupt_packets,
+                if audio_data.corrupt_packets == 1 { "" } else { "s" },
+                audio_data.skipped_samples as f64 / audio_data.sample_rate as f64,
+            ).yellow()
+        );
+    }
+}
+
+/// Clips shorter than one FFT window are analyzed as a single zero-padded frame; say so,
+/// since the time axis then has no resolution
+fn warn_if_shorter_than_window(num_samples: usize, fft_size: usize) {
+    if num_samples > 0 && num_samples < fft_size {
+        eprintln!(
+            "{} {}",
+            "".yellow(),
+            format!(
+                "Audio is shorter than the FFT window ({} < {} samples); padding it to one frame",
+                num_samples, fft_size,
+            ).dimmed()
+        );
+    }
+}
+
+/// `--analysis-max-freq` below Nyquist hides whatever is above it from the cutoff and verdict
+fn warn_if_analysis_limited(args: &Args, sample_rate: u32) {
+    if let Some(max_freq) = args.analysis_max_freq.filter(|&f| f < sample_rate as f32 / 2.0) {
+        eprintln!(
+            "{} {}",
+            "".yellow(),
+            format!("Analysis stops at {}Hz; the cutoff and verdict only see content below it", max_freq).dimmed()
+        );
+    }
+}
+
+/// Highest frequency to display: `--freq-max`, lowered to `--analysis-max-freq`
+fn display_freq_max(args: &Args) -> Option<f32> {
+    match (args.freq_max, args.analysis_max_freq) {
+        (Some(max), Some(limit)) => Some(max.min(limit)),
+        (max, limit) => max.or(limit),
+    }
+}
+
+/// Decoder settings shared by full analysis and `--check`
+fn decode_options(args: &Args) -> decoder::DecodeOptions {
+    decoder::DecodeOptions {
+        start_secs: args.start,
+        end_secs: args.end,
+        channel: args.channel_index,
+        apply_gain: args.apply_gain,
+        strict: args.strict,
+    }
+}
+
+/// Spectrogram settings from the command line and config for a `width` x `height` render
+fn spectrogram_options(
+    args: &Args,
+    config: &config::Config,
+    width: u32,
+    height: u32,
+    reference_db: Option<f32>,
+) -> spectrogram::SpectrogramOptions {
+    spectrogram::SpectrogramOptions {
+        window_size: args.fft_size,
+        zero_pad: args.zero_pad.into(),
+        width,
+        height,
+        linear: !args.log.unwrap_or(config.defaults.log_scale),
+        compute_rolloff: args.rolloff.unwrap_or(config.defaults.rolloff),
+        rolloff_metric: args.rolloff_metric,
+        rolloff_smooth: args.rolloff_smooth as usize,
+        compute_centroid: args.centroid,
+        centroid_smooth: args.centroid_smooth as usize,
+        // Only the JSON summary reports entropy
+        compute_entropy: args.json || args.sidecar,
+        compute_noise_floor: args.noise_floor,
+        // The exported series follow the saved image, not the supersampled render
+        series_width: args.series_csv.is_some().then_some(width / args.supersample),
+        amplitude: args.amplitude,
+        color_curve: args.color_curve,
+        normalize: args.normalize,
+        time_mapping: args.time_mapping,
+        silence_db: args.silence_db.unwrap_or(config.defaults.silence_db),
+        cutoff_db: args.cutoff_line,
+        a_weight: args.a_weight,
+        tilt_db_per_octave: args.tilt,
+        freq_min: args.freq_min,
+        freq_max: display_freq_max(args),
+        analysis_max_freq: args.analysis_max_freq,
+        bass_detail: args.bass_detail,
+        sharpen: args.sharpen,
+        mode: args.mode,
+        fft_gate_db: args.fft_gate,
+        dc_bins: args.dc_bins.into(),
+        render_image: !args.barcode,
+        keep_levels: args.bit_depth == BitDepth::Sixteen,
+        floor_color: args.floor_color,
+        dither: args.dither,
+        reference_db: absolute_reference_db(args).or(reference_db),
+    }
+}
+
+/// Top of the color scale (dBFS) fixed by `--normalize absolute`, if selected
+fn absolute_reference_db(args: &Args) -> Option<f32> {
+    (args.normalize == Normalization::Absolute).then(|| args.ref_db.unwrap_or(0.0))
+}
+
+/// Decode, analyze and render one file, then display or save the result.
+/// `reference_db` pins the top of the color scale (a batch's shared level) instead of this file's own.
+fn analyze(args: &Args, file: &Path, mut config: config::Config, reference_db: Option<f32>) -> Result<()> {
+    let total_start = Instant::now();
+    let quiet = args.quiet;
 
     // Merge CLI args with config defaults (CLI takes priority)
     let use_log = args.log.unwrap_or(config.defaults.log_scale);
     let use_rolloff = args.rolloff.unwrap_or(config.defaults.rolloff);
     let use_verbose = args.verbose.unwrap_or(config.defaults.verbose);
-    let width = args.width.unwrap_or(config.defaults.width);
-    let height = args.height.unwrap_or(config.defaults.height);
+    let mut width = args.width.unwrap_or(config.defaults.width);
+    let height = args.height.unwrap_or(if args.barcode { BARCODE_HEIGHT } else { config.defaults.height });
+    let silence_db = args.silence_db.unwrap_or(config.defaults.silence_db);
     
-    // Handle palette: CLI > config > default
+    // Handle palette: CLI palette file > CLI palette > custom [colors] stops > config default palette
     let palette = args.palette.unwrap_or_else(|| config::parse_palette(&config.defaults.palette));
-    
-    // Apply palette
-    config.colors.stops = config::get_palette_stops(palette);
+    let palette_label = if args.mode == SpectrogramMode::Phase {
+        config.colors.stops = config::phase_wheel_stops();
+        "phase".to_string()
+    } else if let Some(ref path) = args.palette_file {
+        config.colors.stops = palette_file::load(path)?;
+        path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "custom".to_string())
+    } else if config.colors.custom && args.palette.is_none() {
+        "custom".to_string()
+    } else {
+        // Apply palette
+        config.colors.stops = config::get_palette_stops(palette);
+        palette_name(palette)
+    };
 
-    if !args.quiet {
-        print_header();
+    let compact = compact_layout(args);
+    if !quiet {
+        print_header(compact);
+    }
+
+    // A palette may stretch close colors over a wide range, e.g. a slow fade from black
+    if args.mode == SpectrogramMode::Magnitude && !args.dither
+        && args.bit_depth == BitDepth::Eight && !quiet
+    {
+        let band = spectrogram::widest_color_band(&config.colors.stops);
+        if band > BANDING_WARN_FRACTION {
+            eprintln!(
+                "{} {}",
+                "".yellow(),
+                format!(
+                    "Palette has bands of one color over {:.1}% of the scale; use --dither (or --bit-depth 16) for smooth gradients",
+                    band * 100.0,
+                ).dimmed(),
+            );
+        }
+    }
+
+    if args.octaves && !use_log && !quiet {
+        eprintln!("{} {}", "".yellow(), "--octaves only applies to the log frequency scale".dimmed());
+    }
+
+
+
+    // `--save -` streams PNG bytes to stdout, which must then carry nothing else
+    let save_to_stdout = args.save.as_deref() == Some(Path::new("-"));
+    if save_to_stdout {
+        for (flag, set) in [("--json", args.json), ("--verdict", args.verdict), ("--reference", args.reference.is_some()), ("--sidecar", args.sidecar)] {
+            if set {
+                anyhow::bail!("{} cannot be combined with --save - (stdout carries the image)", flag);
+            }
+        }
     }
 
+    if let (Some(min), Some(max)) = (args.freq_min, args.freq_max) {
+        if max <= min {
+            anyhow::bail!("--freq-max ({}Hz) must be above --freq-min ({}Hz)", max, min);
+        }
+    }
+
+    // Check the output format before spending time on analysis
+    let save_target = args.save.as_deref()
+        .filter(|_| !save_to_stdout)
+        .map(|path| resolve_save_path(path, quiet))
+        .transpose()?;
+    if args.bit_depth == BitDepth::Sixteen {
+        if args.mode == SpectrogramMode::Phase {
+            anyhow::bail!("--bit-depth 16 only applies to magnitude mode, not --mode phase");
+        }
+        if let Some((ref path, format)) = save_target {
+            if !matches!(format, ImageFormat::Png | ImageFormat::Tiff) {
+                anyhow::bail!("--bit-depth 16 needs a .png or .tiff output, not {}", path.display());
+            }
+        }
+    }
 
+    if let (Some(start), Some(end)) = (args.start, args.end) {
+        if end <= start {
+            anyhow::bail!("--end ({}s) must be after --start ({}s)", end, start);
+        }
+    }
+
+    // --pps widths are capped at max_width later, so only explicit sizes can fail here
+    check_dimensions(width, height, args.supersample, &config.defaults)?;
+
+    let reference_curve = args.reference.as_deref()
+        .map(reference::ReferenceCurve::load)
+        .transpose()?;
+    let overlay_series = args.overlay_series.as_deref()
+        .map(report::SeriesOverlay::load)
+        .transpose()?;
+
+    // Terminal progress bars, unless quiet
+    let terminal_progress = progress::TerminalProgress::new();
+    let report_progress = |stage, fraction| terminal_progress.update(stage, fraction);
+    let progress: Option<&progress::ProgressCallback> = if quiet { None } else { Some(&report_progress) };
 
     // Decode audio
     let decode_start = Instant::now();
-    let audio_data = decoder::decode_file(&args.file, args.quiet)
-        .context("Failed to decode audio file. Ensure it's a valid audio format (FLAC, MP3, WAV, ALAC, AAC).")?;
+    let decoded = decoder::decode_with_options(file, &decode_options(args), progress)
+        .context("Failed to decode audio file. Ensure it's a valid audio format (FLAC, MP3, WAV, ALAC, AAC) or a video file with an audio track (MP4, MKV, WebM).")?;
+    // Peaks describe the whole file, whichever channel is analyzed
+    let peaks = decoded.peaks();
+    let show_metadata = !quiet && !args.no_metadata;
+    // Loudness is a full filtering pass, so only measure it when it's used or shown
+    let loudness_lufs = (args.loudness_normalize.is_some() || show_metadata || args.json || args.sidecar)
+        .then(|| decoded.integrated_loudness())
+        .flatten();
+    let mut audio_data = decoded.into_mono(args.channel_index)?;
+    audio_data.loudness_lufs = loudness_lufs;
     let decode_time = decode_start.elapsed();
+    if args.reverse {
+        audio_data.samples.reverse();
+    }
 
-    if !args.quiet {
-        println!();
-        print_metadata(&args.file, &audio_data);
-        println!();
-        println!("{}", "Generating spectrogram...".cyan());
+    if audio_data.non_finite_samples > 0 && !quiet {
+        eprintln!(
+            "{} {}",
+            "".yellow(),
+            format!("Replaced {} NaN/Inf samples with silence", audio_data.non_finite_samples).dimmed()
+        );
+    }
+
+    if !quiet {
+        warn_corrupt_packets(&audio_data);
+        warn_if_shorter_than_window(audio_data.samples.len(), args.fft_size);
+        warn_if_analysis_limited(args, audio_data.sample_rate);
+    }
+
+    if audio_data.stereo.is_some_and(|s| s.is_mono_incompatible()) && !quiet {
+        eprintln!(
+            "{} {}",
+            "".yellow(),
+            "Strongly negative stereo correlation: parts of the mix cancel when summed to mono".dimmed()
+        );
+    }
+
+    if args.apply_gain.is_some() && audio_data.applied_gain_db.is_none() && !quiet {
+        eprintln!("{} {}", "".yellow(), "No ReplayGain tags found; --apply-gain has no effect".dimmed());
+    }
+
+    // Peaks and the metadata describe the file; the spectrogram sees the normalized signal
+    let loudness_gain_db = args.loudness_normalize
+        .and_then(|target| Some(target - audio_data.loudness_lufs?));
+    match (args.loudness_normalize, loudness_gain_db) {
+        (Some(target), Some(gain_db)) => {
+            let gain = 10f32.powf(gain_db / 20.0);
+            audio_data.samples.iter_mut().for_each(|s| *s *= gain);
+            if !quiet {
+                eprintln!(
+                    "{} {}",
+                    "".cyan(),
+                    format!("Normalized to {:.1} LUFS ({:+.1} dB)", target, gain_db).dimmed()
+                );
+            }
+        }
+        (Some(_), None) if !quiet => {
+            eprintln!("{} {}", "".yellow(), "Loudness could not be measured (silent or too short); not normalized".dimmed());
+        }
+        _ => {}
+    }
+
+    // The noise floor comes from the STFT, so with --noise-floor the panel waits for it
+    if show_metadata && !args.noise_floor {
+        eprintln!();
+        print_metadata(file, &audio_data, peaks, None, args.channel_index, compact);
+    }
+    if !quiet {
+        eprintln!();
+        eprintln!("{}", "Generating spectrogram...".cyan());
+    }
+
+    // Everything scaled by duration below needs it positive and finite; zero or unknown
+    // (a stream whose length isn't known) falls back to the configured width
+    let known_duration = audio_data.duration_secs.is_finite() && audio_data.duration_secs > 0.0;
+    if args.gif.is_some() && !known_duration {
+        anyhow::bail!("--gif needs a known, nonzero duration");
+    }
+    if args.pps.is_some() && !known_duration && !quiet {
+        eprintln!("{} {}", "".yellow(), "Duration unknown; --pps ignored".dimmed());
+    }
+
+    // Derive width from duration for a consistent time density across files
+    if let Some(pps) = args.pps.filter(|_| known_duration) {
+        // Leave room for --supersample so the rendered width stays within max_width
+        let max_width = config.defaults.max_width / args.supersample;
+        let derived = (audio_data.duration_secs * pps).round().max(1.0);
+        width = derived.min(max_width as f64) as u32;
+        if derived > max_width as f64 && !quiet {
+            eprintln!(
+                "{} {}",
+                "".yellow(),
+                format!("--pps would give a {}px wide image; capped at {}px", derived, max_width).dimmed()
+            );
+        }
+    }
+
+    // A scrolling GIF is cut from one wide render with `width` pixels per window
+    let gif_frame_width = width;
+    if args.gif.is_some() {
+        let window = args.gif_window.min(audio_data.duration_secs);
+        let strip_width = (width as f64 * audio_data.duration_secs / window).round();
+        let max_width = config.defaults.max_width;
+        width = strip_width.min(max_width as f64) as u32;
+        if strip_width > max_width as f64 && !quiet {
+            eprintln!(
+                "{} {}",
+                "".yellow(),
+                format!("The GIF would need a {}px wide render; capped at {}px, so each frame shows more time", strip_width, max_width).dimmed()
+            );
+        }
+
+        // Every frame is a full render, so refuse before spending the time on the STFT
+        let frame_window = audio_data.duration_secs * gif_frame_width.min(width) as f64 / width as f64;
+        let frames = ((audio_data.duration_secs - frame_window) * args.gif_fps as f64).floor() + 1.0;
+        if frames > GIF_MAX_FRAMES as f64 {
+            anyhow::bail!(
+                "The GIF would need {} frames (at most {}); lower --gif-fps or pick a shorter span with --start/--end",
+                frames, GIF_MAX_FRAMES
+            );
+        }
+    }
+
+    // The slice can only be checked against Nyquist once the sample rate is known
+    let (freq_low, freq_high) = spectrogram::display_range(audio_data.sample_rate, !use_log, args.freq_min, display_freq_max(args));
+    if freq_high <= freq_low {
+        anyhow::bail!(
+            "Frequency range {}-{}Hz is empty for this file (Nyquist is {}Hz{})",
+            freq_low, freq_high, audio_data.sample_rate / 2,
+            if use_log { ", log scale starts at 20Hz" } else { "" }
+        );
     }
 
     // Determine dimensions
     let (term_w, term_h) = size().unwrap_or((80, 24));
     
     let stft_start = Instant::now();
+    // Supersampling renders everything larger and shrinks the finished image back down
+    let spectrogram_options = spectrogram_options(args, &config, width * args.supersample, height * args.supersample, reference_db);
     let spectrogram_result = spectrogram::generate_spectrogram(
         &audio_data.samples,
         audio_data.sample_rate,
-        width,
-        height,
         &config,
-        !use_log,  // linear = !log
-        args.quiet,
-        use_rolloff,
+        &spectrogram_options,
+        progress,
     )?;
     let stft_time = stft_start.elapsed();
+    if show_metadata && args.noise_floor {
+        eprintln!();
+        let noise_floor = Some(spectrogram_result.noise_floor_db);
+        print_metadata(file, &audio_data, peaks, noise_floor, args.channel_index, compact);
+    }
+
+    let (gated_frames, num_time_frames) = (spectrogram_result.stft.gated_frames, spectrogram_result.stft.num_time_frames);
+    let spectrum = analysis::analyze_spectrum(&spectrogram_result.stft, audio_data.sample_rate, silence_db);
+    let fingerprint = args.fingerprint.then(|| analysis::fingerprint(&spectrogram_result.stft, audio_data.sample_rate));
+
+    let reference_deviation = reference_curve.as_ref()
+        .map(|curve| reference::compare_to_reference(&spectrum.average_spectrum, audio_data.sample_rate, args.analysis_max_freq, curve));
+
+    let max_freq_energy = args.max_freq_energy.map(|threshold_db| analysis::MaxFreqEnergy {
+        threshold_db,
+        freq_hz: analysis::highest_frequency_above(&spectrum.average_spectrum, audio_data.sample_rate, threshold_db),
+    });
+
+    let harmonics = args.harmonics
+        .then(|| analysis::detect_harmonics(&spectrum.average_spectrum, audio_data.sample_rate))
+        .flatten();
+
+    let tonal_peaks = args.peaks
+        .map(|count| analysis::detect_tonal_peaks(&spectrum.average_spectrum, audio_data.sample_rate, count as usize));
+
+    let mut summary = report::AnalysisSummary::new(
+        file,
+        &audio_data,
+        peaks,
+        report::AnalysisParams {
+            width,
+            height,
+            freq_scale: if use_log { "log" } else { "linear" },
+            palette: palette_label.clone(),
+            fft_size: args.fft_size,
+            zero_pad: args.zero_pad.into(),
+            hop_size: spectrogram::hop_size(args.fft_size),
+            overlap: 1.0 - spectrogram::hop_size(args.fft_size) as f32 / args.fft_size as f32,
+            window: spectrogram::WINDOW_FUNCTION,
+            amplitude: value_name(args.amplitude),
+            color_curve: value_name(args.color_curve),
+            db_range: spectrogram::DB_RANGE,
+            normalization: value_name(args.normalize),
+            time_mapping: value_name(args.time_mapping),
+            dc_bins_removed: args.dc_bins.into(),
+            silence_db,
+            a_weighting: args.a_weight,
+            reversed: args.reverse,
+            tilt_db_per_octave: args.tilt,
+            reference_db: spectrogram_options.reference_db,
+            rolloff_metric: value_name(args.rolloff_metric),
+            freq_min_hz: freq_low,
+            freq_max_hz: freq_high,
+        },
+        spectrogram_result.rolloff_frequencies.as_deref(),
+        &spectrum,
+    );
+    summary.loudness_gain_db = loudness_gain_db;
+    summary.reference = reference_deviation.clone();
+    summary.spectral_flux = report::FluxStats::from_flux(
+        &spectrogram::compute_spectral_flux(&spectrogram_result.stft),
+        audio_data.sample_rate as f32 / spectrogram::hop_size(args.fft_size) as f32,
+    );
+    if spectrogram_options.compute_entropy {
+        summary.spectral_entropy = report::EntropyStats::from_entropy(
+            &spectrogram::frame_entropy(&spectrogram_result.frame_stats),
+        );
+    }
+    summary.harmonics = harmonics.clone();
+    summary.tonal_peaks = tonal_peaks.clone();
+    summary.max_freq_energy = max_freq_energy;
+    summary.noise_floor_db = spectrogram_result.noise_floor_db;
+    summary.fingerprint = fingerprint.map(report::fingerprint_hex);
+
+    if let (Some(path), Some(series)) = (&args.series_csv, &spectrogram_result.series) {
+        report::write_series_csv(path, series, audio_data.duration_secs)?;
+        if !quiet {
+            eprintln!("{} Saved series to {}", "".green().bold(), path.display().to_string().cyan());
+        }
+    }
 
     let render_start = Instant::now();
     let render_options = render::RenderOptions {
         linear: !use_log,
         show_rolloff: use_rolloff,
         rolloff_frequencies: spectrogram_result.rolloff_frequencies,
+        rolloff_label: args.rolloff_label,
+        centroid_frequencies: spectrogram_result.centroid_frequencies,
+        cutoff_frequencies: spectrogram_result.cutoff_frequencies,
+        // One value per column of the (supersampled) spectrogram, like the lines above
+        overlay_frequencies: overlay_series
+            .map(|series| series.lines(audio_data.duration_secs, (width * args.supersample) as usize))
+            .unwrap_or_default(),
+        amplitude: args.amplitude,
+        color_curve: args.color_curve,
+        show_octaves: args.octaves,
+        time_grid: args.time_grid,
+        time_ticks: args.time_ticks,
+        time_offset_secs: 0.0,
+        a_weighted: args.a_weight,
+        tilt_db_per_octave: args.tilt,
+        freq_unit: args.freq_unit,
+        harmonic_frequencies: harmonics.as_ref().map(|h| {
+            std::iter::once(h.fundamental_hz)
+                .chain(h.harmonics.iter().map(|harmonic| harmonic.freq_hz))
+                .collect()
+        }),
+        tonal_peaks: tonal_peaks.clone(),
+        freq_min: args.freq_min,
+        freq_max: display_freq_max(args),
+        text_outline: args.text_outline,
+        palette_name: palette_label,
+        legend_ticks: args.legend_ticks,
+        // The color bar costs columns a terminal can't spare; saved images keep it
+        show_legend: args.legend.unwrap_or(args.save.is_some() || args.gif.is_some()),
+        phase: args.mode == SpectrogramMode::Phase,
+        noise_floor_level: spectrogram_result.noise_floor_level,
+        scale_top_db: spectrogram_options.reference_db,
+        margins: args.margins.unwrap_or_default(),
+        supersample: args.supersample,
     };
-    let final_img = render::prepare_final_image(
-        spectrogram_result.image, 
-        audio_data.sample_rate, 
-        audio_data.duration_secs, 
-        &config, 
-        render_options,
-    )?;
-    let render_time = render_start.elapsed();
+    if let Some(ref plot_path) = args.spectrum_plot {
+        let plot = render::render_spectrum_plot(
+            &spectrum.average_spectrum,
+            audio_data.sample_rate,
+            gif_frame_width,
+            args.height.unwrap_or(config.defaults.height),
+            &config,
+            &render_options,
+        )?;
+        plot.save(plot_path)
+            .with_context(|| format!("Failed to save spectrum plot to {:?}", plot_path))?;
+        if !quiet {
+            eprintln!("{} Saved spectrum plot to {}", "".green().bold(), plot_path.display().to_string().cyan());
+        }
+    }
 
+    let render_time = if let Some(ref gif_path) = args.gif {
+        let frame_count = write_scrolling_gif(
+            gif_path,
+            &spectrogram_result.image,
+            gif_frame_width,
+            audio_data.duration_secs,
+            args.gif_fps,
+            args.bare,
+            audio_data.sample_rate,
+            &config,
+            &render_options,
+        )?;
+        if !quiet {
+            eprintln!();
+            eprintln!("{} Saved {}-frame animation to {}", "".green().bold(), frame_count, gif_path.display().to_string().cyan());
+        }
+        render_start.elapsed()
+    } else {
+        // 16-bit output recolors the spectrogram pixels left untouched by overlays
+        let plain_spectrogram = spectrogram_result.levels.as_ref().map(|_| spectrogram_result.image.clone());
+        // Only the framed spectrogram has margins
+        let margins = if args.bare || args.barcode { render::Margins::default() } else { render_options.margins };
+        // Bare mode: exactly width x height, no axes, legend, or overlays
+        let final_img = if args.barcode {
+            let strip = spectrogram::render_barcode(&spectrum.average_spectrum, audio_data.sample_rate, &config, &spectrogram_options);
+            if args.bare {
+                strip
+            } else {
+                render::prepare_barcode_image(strip, audio_data.sample_rate, &config, &render_options)?
+            }
+        } else if args.bare {
+            render::downsample(spectrogram_result.image, args.supersample)
+        } else {
+            render::prepare_final_image(
+                spectrogram_result.image, 
+                audio_data.sample_rate, 
+                audio_data.duration_secs, 
+                &config, 
+                render_options,
+            )?
+        };
+        let dynamic_img = match (spectrogram_result.levels, plain_spectrogram) {
+            (Some(levels), Some(plain)) => {
+                let deep = spectrogram::colorize_levels_16(
+                    &levels, plain.width(), plain.height(), &config.colors.stops, args.floor_color,
+                );
+                // Shrunk the same way, pixels away from the overlays still match the final image
+                let (plain, deep) = (render::downsample(plain, args.supersample), render::downsample(deep, args.supersample));
+                image::DynamicImage::ImageRgb16(render::deepen_image(&final_img, &plain, &deep, margins))
+            }
+            _ => image::DynamicImage::ImageRgb8(final_img),
+        };
+        let render_time = render_start.elapsed();
 
+        // Handle save option
+        if save_to_stdout {
+            write_png_to_stdout(&dynamic_img)?;
+        } else if let Some((ref save_path, format)) = save_target {
+            dynamic_img.save_with_format(save_path, format)
+                .with_context(|| format!("Failed to save image to {:?}", save_path))?;
+            if !quiet {
+                eprintln!();
+                eprintln!("{} Saved to {}", "".green().bold(), save_path.display().to_string().cyan());
+            }
 
-    let dynamic_img = image::DynamicImage::ImageRgb8(final_img);
+            if args.sidecar {
+                let sidecar = report::write_sidecar(save_path, &summary)?;
+                if !quiet {
+                    eprintln!("{} Saved summary to {}", "".green().bold(), sidecar.display().to_string().cyan());
+                }
+            }
+        } else if !args.json {
+            if !quiet {
+                eprintln!();
+                if !compact {
+                    print_separator();
+                    eprintln!();
+                }
+            }
 
-    // Handle save option
-    if let Some(ref save_path) = args.save {
-        dynamic_img.save(save_path)
-            .with_context(|| format!("Failed to save image to {:?}", save_path))?;
-        if !args.quiet {
-            println!();
-            println!("{} Saved to {}", "".green().bold(), save_path.display().to_string().cyan());
+            let (cells_w, cells_h) = terminal_cells(dynamic_img.width(), dynamic_img.height(), term_w, term_h, args.fit);
+            let viuer_conf = ViuerConfig {
+                width: Some(cells_w),
+                height: Some(cells_h),
+                absolute_offset: false,
+                transparent: false,
+                ..Default::default()
+            };
+
+            viuer::print(&dynamic_img, &viuer_conf)?;
         }
-    } else {
-        if !args.quiet {
+        render_time
+    };
+
+    if args.json {
+        println!("{}", if args.batch { report::to_json_line(&summary)? } else { summary.to_json()? });
+    } else if args.verdict {
+        println!();
+        print_verdict(&spectrum);
+    }
+
+    if let (Some(curve), Some(deviation)) = (&reference_curve, &reference_deviation) {
+        if !args.json {
             println!();
-            print_separator();
+            print_reference(curve, deviation);
+        }
+    }
+
+    if let Some(ref harmonics) = harmonics {
+        // With `--save -` the markers are still drawn, but stdout only carries the image
+        if !args.json && !save_to_stdout {
             println!();
+            print_harmonics(harmonics);
         }
+    }
 
-        let viuer_conf = ViuerConfig {
-            width: Some(term_w as u32),
-            height: Some(term_h as u32),
-            absolute_offset: false,
-            transparent: false,
-            ..Default::default()
-        };
+    if let Some(ref peaks) = tonal_peaks {
+        if !args.json && !save_to_stdout {
+            println!();
+            print_tonal_peaks(peaks);
+        }
+    }
 
-        viuer::print(&dynamic_img, &viuer_conf)?;
+    // Concise one-line summary of the analysis
+    if !quiet {
+        eprintln!();
+        eprintln!("{}", format_summary_line(&audio_data, &spectrum, max_freq_energy, fingerprint));
     }
 
     // Print timing statistics if verbose
     if use_verbose {
         let total_time = total_start.elapsed();
-        println!();
-        println!("{}", " Timing Statistics".bright_magenta().bold());
-        println!("  {} {:>8.2?}", "Decoding:".dimmed(), decode_time);
-        println!("  {} {:>8.2?}", "STFT:    ".dimmed(), stft_time);
-        println!("  {} {:>8.2?}", "Render:  ".dimmed(), render_time);
-        println!("  {} {:>8.2?}", "Total:   ".bright_white().bold(), total_time);
+        eprintln!();
+        eprintln!("{}", " Timing Statistics".bright_magenta().bold());
+        eprintln!("  {} {:>8.2?}", "Decoding:".dimmed(), decode_time);
+        eprintln!("  {} {:>8.2?}", "STFT:    ".dimmed(), stft_time);
+        if args.fft_gate.is_some() {
+            eprintln!(
+                "  {} {:>8} of {} frames below the gate, FFT skipped",
+                "Gated:   ".dimmed(), gated_frames, num_time_frames
+            );
+        }
+        eprintln!("  {} {:>8.2?}", "Render:  ".dimmed(), render_time);
+        eprintln!("  {} {:>8.2?}", "Total:   ".bright_white().bold(), total_time);
+    }
+
+    Ok(())
+}
+
+/// Compose frames that slide a `frame_width` window across the rendered `strip` in real time
+/// and encode them as a looping GIF. Per-column overlays are sliced to each window.
+/// Returns the number of frames written.
+#[allow(clippy::too_many_arguments)]
+fn write_scrolling_gif(
+    path: &Path,
+    strip: &image::RgbImage,
+    frame_width: u32,
+    duration_secs: f64,
+    fps: u32,
+    bare: bool,
+    sample_rate: u32,
+    config: &config::Config,
+    render_options: &render::RenderOptions,
+) -> Result<usize> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+    use image::{Delay, Frame};
+
+    let frame_width = frame_width.min(strip.width());
+    let px_per_sec = strip.width() as f64 / duration_secs;
+    let window_secs = frame_width as f64 / px_per_sec;
+    let step_px = px_per_sec / fps as f64;
+    let frame_count = ((strip.width() - frame_width) as f64 / step_px).floor() as usize + 1;
+
+    let file = fs::File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+    let mut encoder = GifEncoder::new_with_speed(std::io::BufWriter::new(file), GIF_QUANTIZE_SPEED);
+    encoder.set_repeat(Repeat::Infinite).context("Failed to write GIF header")?;
+
+    let slice_line = |line: &[f32], x0: usize| line[x0..(x0 + frame_width as usize).min(line.len())].to_vec();
+    let slice = |series: &Option<Vec<f32>>, x0: usize| series.as_deref().map(|s| slice_line(s, x0));
+    for i in 0..frame_count {
+        progress::check_cancelled()?;
+        let x0 = ((i as f64 * step_px).round() as u32).min(strip.width() - frame_width);
+        let view = image::imageops::crop_imm(strip, x0, 0, frame_width, strip.height()).to_image();
+        let img = if bare {
+            view
+        } else {
+            let options = render::RenderOptions {
+                time_offset_secs: x0 as f64 / px_per_sec,
+                rolloff_frequencies: slice(&render_options.rolloff_frequencies, x0 as usize),
+                centroid_frequencies: slice(&render_options.centroid_frequencies, x0 as usize),
+                cutoff_frequencies: slice(&render_options.cutoff_frequencies, x0 as usize),
+                overlay_frequencies: render_options.overlay_frequencies.iter()
+                    .map(|line| slice_line(line, x0 as usize))
+                    .collect(),
+                ..render_options.clone()
+            };
+            render::prepare_final_image(view, sample_rate, window_secs, config, options)?
+        };
+
+        let frame = Frame::from_parts(
+            image::DynamicImage::ImageRgb8(img).into_rgba8(),
+            0,
+            0,
+            Delay::from_numer_denom_ms(1000, fps),
+        );
+        encoder.encode_frame(frame).with_context(|| format!("Failed to write GIF frame to {:?}", path))?;
+    }
+
+    Ok(frame_count)
+}
+
+fn styled_verdict(verdict: analysis::Verdict) -> String {
+    let description = verdict.description();
+    match verdict {
+        analysis::Verdict::LikelyLossless => description.green().bold().to_string(),
+        analysis::Verdict::Silent => description.dimmed().to_string(),
+        _ => description.yellow().bold().to_string(),
+    }
+}
+
+fn print_verdict(spectrum: &analysis::SpectrumAnalysis) {
+    let verdict = styled_verdict(spectrum.verdict);
+
+    match spectrum.cutoff_hz {
+        Some(cutoff) => println!(
+            "{} {} {}",
+            "Verdict:".cyan(),
+            verdict,
+            format!("(cutoff ~{:.1} kHz)", cutoff / 1000.0).dimmed()
+        ),
+        None => println!("{} {}", "Verdict:".cyan(), verdict),
+    }
+}
+
+/// e.g. "44.1kHz · FLAC · 3:21 · est. cutoff ~21kHz · likely lossless"
+fn format_summary_line(
+    audio_data: &decoder::AudioData,
+    spectrum: &analysis::SpectrumAnalysis,
+    max_freq_energy: Option<analysis::MaxFreqEnergy>,
+    fingerprint: Option<u64>,
+) -> String {
+    let mut parts = vec![
+        format!("{}kHz", audio_data.sample_rate as f32 / 1000.0),
+        audio_data.metadata.codec.clone(),
+        format_duration(audio_data.duration_secs)
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    ];
+    if let Some(cutoff) = spectrum.cutoff_hz {
+        parts.push(format!("est. cutoff ~{:.0}kHz", cutoff / 1000.0));
+    }
+    if let Some(analysis::MaxFreqEnergy { threshold_db, freq_hz: Some(freq) }) = max_freq_energy {
+        parts.push(format!("energy up to {:.1}kHz ({}dB)", freq / 1000.0, threshold_db));
+    }
+    if let Some(fingerprint) = fingerprint {
+        parts.push(format!("fp {}", report::fingerprint_hex(fingerprint)));
+    }
+
+    format!("{} {} {}", parts.join(" · ").bright_white(), "·".bright_white(), styled_verdict(spectrum.verdict))
+}
+
+fn print_reference(curve: &reference::ReferenceCurve, deviation: &[reference::ReferenceDeviation]) {
+    let name = curve.name.as_deref().unwrap_or("reference");
+    println!("{} {}", " Reference:".bright_magenta().bold(), name);
+    println!("  {:>9}  {:>9}  {:>9}  {:>9}", "Freq".dimmed(), "Target".dimmed(), "Measured".dimmed(), "Dev".dimmed());
+    for d in deviation {
+        let dev = format!("{:+8.1}dB", d.deviation_db);
+        let dev = if d.deviation_db.abs() > 3.0 { dev.yellow().to_string() } else { dev.green().to_string() };
+        println!("  {:>7.0}Hz  {:>7.1}dB  {:>7.1}dB  {}", d.freq_hz, d.reference_db, d.measured_db, dev);
+    }
+}
+
+fn print_harmonics(harmonics: &analysis::HarmonicAnalysis) {
+    println!(
+        "{} {:.1}Hz {}",
+        " Fundamental:".bright_magenta().bold(),
+        harmonics.fundamental_hz,
+        format!("(THD ~{:.3}%)", harmonics.thd_percent).dimmed()
+    );
+    for h in &harmonics.harmonics {
+        println!("  {:>3}  {:>9.1}Hz  {:>7.1}dB", format!("{}f", h.order).dimmed(), h.freq_hz, h.level_db);
+    }
+}
+
+fn print_tonal_peaks(peaks: &[analysis::TonalPeak]) {
+    println!("{}", " Tonal Peaks".bright_magenta().bold());
+    if peaks.is_empty() {
+        println!("  {}", "none found".dimmed());
     }
+    for (i, peak) in peaks.iter().enumerate() {
+        println!("  {:>3}  {:>9.1}Hz  {:>7.1}dB", format!("{}.", i + 1).dimmed(), peak.freq_hz, peak.level_db);
+    }
+}
+
+fn palette_name(palette: Palette) -> String {
+    value_name(palette)
+}
+
+/// The name a `ValueEnum` value is given on the command line
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
 
+fn print_palettes() {
+    const PREVIEW_WIDTH: usize = 40;
+
+    println!("{}", " Color Palettes".bright_white().bold());
+    let registry = config::PaletteRegistry::builtin();
+    for name in registry.names() {
+        let gradient = spectrogram::create_gradient_map(
+            registry.get(name).unwrap_or_default(),
+            PREVIEW_WIDTH,
+        );
+        let bar: String = gradient.iter()
+            .map(|c| " ".on_truecolor(c[0], c[1], c[2]).to_string())
+            .collect();
+        println!("  {:<12} {}", name.cyan(), bar);
+    }
+}
+
+fn print_formats(json: bool) -> Result<()> {
+    let formats = decoder::supported_formats();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&formats).context("Failed to serialize format list")?);
+        return Ok(());
+    }
+
+    // Plain columns without color so scripts can grep/awk the output
+    println!("Containers:");
+    for format in &formats.containers {
+        println!("  {:<14} {:<42} {}", format.name, format.description, format.extensions.join(","));
+    }
+    println!("Codecs:");
+    for codec in &formats.codecs {
+        println!("  {:<14} {}", codec.name, codec.description);
+    }
     Ok(())
 }
 
-fn print_header() {
-    println!();
-    println!("{}", "───────────────────────────────────────────────────────".bright_blue());
-    println!("   {}", " Spek-CLI  Audio Spectrum Analyzer".bright_white().bold());
-    println!("{}", "───────────────────────────────────────────────────────".bright_blue());
-    println!();
+/// Terminal cells (columns, rows) to show an image in. Each cell holds two pixels stacked
+/// vertically, so a fitted image keeps its aspect ratio at twice the row count in pixels.
+fn terminal_cells(img_w: u32, img_h: u32, term_w: u16, term_h: u16, fit: TerminalFit) -> (u32, u32) {
+    // Leave a row for the prompt
+    let (cols, rows) = (term_w.max(1) as f64, term_h.saturating_sub(1).max(1) as f64);
+    match fit {
+        TerminalFit::Stretch => (cols as u32, rows as u32),
+        TerminalFit::Fit => {
+            let scale = (cols / img_w as f64).min(2.0 * rows / img_h as f64);
+            let w = (img_w as f64 * scale).floor().max(1.0);
+            let h = (img_h as f64 * scale / 2.0).round().clamp(1.0, rows);
+            (w as u32, h as u32)
+        }
+    }
+}
+
+/// Render the `--palette-preview` swatch, then save it or show it in the terminal
+fn palette_preview(args: &Args, palette: Palette) -> Result<()> {
+    let config = config::Config::default();
+    let (width, height) = (args.width.unwrap_or(512), args.height.unwrap_or(48));
+    check_dimensions(width, height, 1, &config.defaults)?;
+
+    let name = palette_name(palette);
+    let caption = (!args.bare).then_some(name.as_str());
+    let swatch = render::render_palette_swatch(&config::get_palette_stops(palette), caption, width, height, &config)?;
+    let swatch = image::DynamicImage::ImageRgb8(swatch);
+
+    match args.save.as_deref() {
+        Some(path) if path == Path::new("-") => write_png_to_stdout(&swatch),
+        Some(path) => {
+            let (save_path, format) = resolve_save_path(path, args.quiet)?;
+            swatch.save_with_format(&save_path, format)
+                .with_context(|| format!("Failed to save image to {:?}", save_path))?;
+            if !args.quiet {
+                eprintln!("{} Saved to {}", "".green().bold(), save_path.display().to_string().cyan());
+            }
+            Ok(())
+        }
+        None => {
+            viuer::print(&swatch, &ViuerConfig { absolute_offset: false, ..Default::default() })?;
+            Ok(())
+        }
+    }
+}
+
+/// Reject zero or oversized image dimensions before anything is allocated.
+/// The limits apply to the rendered size, i.e. after `--supersample`.
+fn check_dimensions(width: u32, height: u32, supersample: u32, limits: &config::DefaultSettings) -> Result<()> {
+    if width == 0 || height == 0 {
+        anyhow::bail!("Image dimensions must be nonzero (got {}x{})", width, height);
+    }
+    let supersampled = |size: u32| match supersample {
+        1 => String::new(),
+        factor => format!(" at --supersample {} ({}px)", factor, size as u64 * factor as u64),
+    };
+    if width.checked_mul(supersample).is_none_or(|w| w > limits.max_width) {
+        anyhow::bail!("--width {}{} exceeds the maximum of {} (raise max_width in the config to allow it)", width, supersampled(width), limits.max_width);
+    }
+    if height.checked_mul(supersample).is_none_or(|h| h > limits.max_height) {
+        anyhow::bail!("--height {}{} exceeds the maximum of {} (raise max_height in the config to allow it)", height, supersampled(height), limits.max_height);
+    }
+    Ok(())
+}
+
+/// Extensions whose encoders can write an 8-bit RGB image
+/// (excludes e.g. .pbm/.pgm, which are bilevel/grayscale only)
+const SAVE_EXTENSIONS: [&str; 11] = [
+    "png", "jpg", "jpeg", "bmp", "tiff", "tif", "tga", "ppm", "pam", "gif", "qoi",
+];
+
+/// Most frames a scrolling GIF may have (10 minutes at 10 fps)
+const GIF_MAX_FRAMES: u32 = 6_000;
+
+/// NeuQuant speed for GIF color quantization (1 = best, 30 = fastest)
+const GIF_QUANTIZE_SPEED: i32 = 20;
+
+/// Determine the output format from the file extension.
+/// A path without an extension gets ".png" appended.
+fn resolve_save_path(path: &Path, quiet: bool) -> Result<(PathBuf, ImageFormat)> {
+    let extension = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ext,
+        None => {
+            let png_path = path.with_extension("png");
+            if !quiet {
+                eprintln!(
+                    "{} {}",
+                    "".yellow(),
+                    format!("No file extension given, saving as PNG: {}", png_path.display()).dimmed()
+                );
+            }
+            return Ok((png_path, ImageFormat::Png));
+        }
+    };
+
+    let format = ImageFormat::from_extension(extension)
+        .filter(|_| SAVE_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+    match format {
+        Some(format) => Ok((path.to_path_buf(), format)),
+        None => anyhow::bail!(
+            "Cannot save images as .{} (supported: {})",
+            extension,
+            SAVE_EXTENSIONS.join(", ")
+        ),
+    }
+}
+
+fn write_png_to_stdout(img: &image::DynamicImage) -> Result<()> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut png, image::ImageOutputFormat::Png)
+        .context("Failed to encode PNG")?;
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(png.get_ref())
+        .and_then(|_| stdout.flush())
+        .context("Failed to write PNG to stdout")
+}
+
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(secs),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_positive_f32(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// FFT sizes must be even so bins land exactly on multiples of sample_rate / N
+fn parse_fft_size(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n < 64 => Err("must be at least 64".to_string()),
+        Ok(n) if n % 2 != 0 => Err("must be even (bins are sample_rate / N apart, N / 2 of them up to Nyquist)".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_ref_db(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(db) if db.is_finite() && (-200.0..=20.0).contains(&db) => Ok(db),
+        Ok(_) => Err("must be between -200 and 20 dBFS".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_tilt(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(db) if db.is_finite() && db.abs() <= 12.0 => Ok(db),
+        Ok(_) => Err("must be between -12 and 12 dB per octave".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_margins(value: &str) -> Result<render::Margins, String> {
+    let sides = value.split(',')
+        .map(|v| v.trim().parse::<u32>().map_err(|e| format!("{:?}: {}", v, e)))
+        .collect::<Result<Vec<u32>, String>>()?;
+    let [top, right, bottom, left] = match sides[..] {
+        [all] => [all; 4],
+        [vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+        [top, right, bottom, left] => [top, right, bottom, left],
+        _ => return Err("expected 1, 2 or 4 comma-separated pixel values".to_string()),
+    };
+    Ok(render::Margins { top, right, bottom, left })
+}
+
+fn parse_loudness_target(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(lufs) if (-70.0..=0.0).contains(&lufs) => Ok(lufs),
+        Ok(_) => Err("must be between -70 and 0 LUFS".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_hex_color(value: &str) -> Result<image::Rgb<u8>, String> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("must be a \"#RRGGBB\" hex color".to_string());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string());
+    Ok(image::Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+fn parse_supersample(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(factor @ (1 | 2 | 4)) => Ok(factor),
+        Ok(_) => Err("must be 1, 2 or 4".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_non_negative(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(hz) if hz >= 0.0 && hz.is_finite() => Ok(hz),
+        Ok(_) => Err("must be a non-negative number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Width of the File Information box, the widest fixed-layout output
+const METADATA_BOX_WIDTH: u16 = 58;
+
+/// `--compact`, or a terminal too narrow for the box layout
+fn compact_layout(args: &Args) -> bool {
+    args.compact || size().is_ok_and(|(cols, _)| cols < METADATA_BOX_WIDTH)
+}
+
+fn print_header(compact: bool) {
+    eprintln!();
+    if compact {
+        eprintln!("{}", "Spek-CLI  Audio Spectrum Analyzer".bright_white().bold());
+        eprintln!();
+        return;
+    }
+    eprintln!("{}", "───────────────────────────────────────────────────────".bright_blue());
+    eprintln!("   {}", " Spek-CLI  Audio Spectrum Analyzer".bright_white().bold());
+    eprintln!("{}", "───────────────────────────────────────────────────────".bright_blue());
+    eprintln!();
 }
 
 fn print_separator() {
-    println!("{}", "═══════════════════════════════════════════════════════════".bright_blue().dimmed());
+    eprintln!("{}", "═══════════════════════════════════════════════════════════".bright_blue().dimmed());
 }
 
-fn print_metadata(file_path: &PathBuf, audio_data: &decoder::AudioData) {
-    println!("{}", "┌─ File Information ─────────────────────────────────────┐".bright_blue());
-    print_row("File", &truncate_path(file_path, 42));
-    println!("{}", "├────────────────────────────────────────────────────────┤".bright_blue());
-    print_row("Codec", &format_codec(&audio_data.metadata.codec));
-    print_row("Duration", &format_duration(audio_data.duration_secs));
-    print_row("Sample Rate", &format!("{}Hz", audio_data.sample_rate));
-    print_row("Channels", format_channels(&audio_data.metadata.channel_layout));
+/// `noise_floor` is given with `--noise-floor`; its inner None means nothing but digital silence.
+/// `compact` prints plain `key: value` lines instead of the box.
+fn print_metadata(
+    file_path: &Path,
+    audio_data: &decoder::AudioData,
+    peaks: analysis::PeakLevels,
+    noise_floor: Option<Option<f32>>,
+    channel_index: Option<usize>,
+    compact: bool,
+) {
+    let mut rows: Vec<(&str, String)> = Vec::new();
+    let mut row = |label, value: String| rows.push((label, value));
+
+    if let Some(ref container) = audio_data.metadata.container {
+        row("Container", container.clone());
+    }
+    row("Codec", format_codec(&audio_data.metadata.codec));
+    row("Duration", format_duration(audio_data.duration_secs));
+    row("Sample Rate", format!("{}Hz", audio_data.sample_rate));
+    row("Channels", format_channels(&audio_data.metadata.channel_layout).to_string());
+    if let Some(index) = channel_index {
+        row("Analyzed", format!("Channel {} only", index));
+    }
 
     if let Some(bps) = audio_data.metadata.bits_per_sample {
-        print_row("Bit Depth", &format!("{} bits", bps));
+        row("Bit Depth", format!("{} bits", bps));
     }
 
     if let Some(br) = audio_data.metadata.bit_rate {
-        print_row("Bit Rate", &format_bitrate(br));
+        row("Bit Rate", format_bitrate(br));
     }
 
-    println!("{}", "└────────────────────────────────────────────────────────┘".bright_blue());
+    let replay_gain = &audio_data.metadata.replay_gain;
+    if let Some(gain) = replay_gain.track_gain_db {
+        row("Track Gain", format!("{:+.2} dB", gain));
+    }
+    if let Some(gain) = replay_gain.album_gain_db {
+        row("Album Gain", format!("{:+.2} dB", gain));
+    }
+    if let Some(gain) = audio_data.applied_gain_db {
+        row("Applied Gain", format!("{:+.2} dB", gain));
+    }
+
+    if let Some(lufs) = audio_data.loudness_lufs {
+        row("Loudness", format!("{:.1} LUFS", lufs));
+    }
+    row("Sample Peak", format!("{:.2} dBFS", peaks.sample_peak_dbfs));
+    let true_peak = format!("{:.2} dBTP", peaks.true_peak_dbtp);
+    if peaks.is_over() {
+        row("True Peak", format!("{} (over)", true_peak));
+    } else {
+        row("True Peak", true_peak);
+    }
+    if let Some(stereo) = audio_data.stereo {
+        row("Stereo", format!(
+            "{:+.2} correlation, {:.0}% out of phase",
+            stereo.correlation, stereo.out_of_phase * 100.0,
+        ));
+    }
+    match noise_floor {
+        Some(Some(db)) => row("Noise Floor", format!("{:.1} dBFS per bin", db)),
+        Some(None) => row("Noise Floor", "none (digital silence)".to_string()),
+        None => {}
+    }
+    let is_warning = |label: &str| match label {
+        "True Peak" => peaks.is_over(),
+        "Stereo" => audio_data.stereo.is_some_and(|s| s.is_mono_incompatible()),
+        _ => false,
+    };
+
+    if compact {
+        eprintln!("{} {}", "File:".cyan(), file_path.display());
+        for (label, value) in rows {
+            let value = if is_warning(label) { value.red().to_string() } else { value };
+            eprintln!("{} {}", format!("{}:", label).cyan(), value);
+        }
+        return;
+    }
+
+    eprintln!("{}", "┌─ File Information ─────────────────────────────────────┐".bright_blue());
+    print_row("File", &truncate_path(file_path, 42));
+    eprintln!("{}", "├────────────────────────────────────────────────────────┤".bright_blue());
+    for (label, value) in rows {
+        if is_warning(label) {
+            // Pad before coloring so the escape codes don't break the box alignment
+            print_row(label, &format!("{:<40}", value).red().to_string());
+        } else {
+            print_row(label, &value);
+        }
+    }
+    eprintln!("{}", "└────────────────────────────────────────────────────────┘".bright_blue());
 }
 
 fn print_row(label: &str, value: &str) {
     use owo_colors::OwoColorize;
     // Label column is 14 chars, value column fills the rest (40 chars)
-    println!("{} {:<14} {:<40}{}", 
+    eprintln!("{} {:<14} {:<40}{}", 
         "│".bright_blue(), 
         format!("{}:", label).cyan(),
         value,
@@ -228,7 +2057,7 @@ fn print_row(label: &str, value: &str) {
     );
 }
 
-fn truncate_path(path: &PathBuf, max_len: usize) -> String {
+fn truncate_path(path: &Path, max_len: usize) -> String {
     let path_str = path.display().to_string();
     if path_str.len() <= max_len {
         path_str
@@ -257,6 +2086,9 @@ fn format_codec(codec: &str) -> String {
 }
 
 fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return "unknown".to_string();
+    }
     let total_seconds = seconds as u64;
     let hours = total_seconds / 3600;
     let minutes = (total_seconds % 3600) / 60;
@@ -0,0 +1,153 @@
+use anyhow::{bail, Result, Context};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use crate::config::{ColorConfig, ColorStop};
+
+/// Offset used to separate two stops that share a position (hard edges in .ggr files)
+const EDGE_OFFSET: f32 = 1e-4;
+
+/// Load color stops from a palette file, detecting the format by extension:
+///
+/// - `.toml`: a `stops = [{ position, color }, ...]` array, as in the `[colors]` config table
+/// - `.ggr`: a GIMP gradient
+/// - `.csv`: one `position,#RRGGBB` row per stop
+///
+/// Positions are clamped to 0.0..=1.0 and the result is validated like a config palette.
+pub fn load(path: &Path) -> Result<Vec<ColorStop>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read palette file: {:?}", path))?;
+
+    let extension = path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let stops = match extension.as_str() {
+        "toml" => parse_toml(&content),
+        "ggr" => parse_ggr(&content),
+        "csv" => parse_csv(&content),
+        _ => bail!("Unsupported palette file {:?} (expected .toml, .ggr or .csv)", path),
+    }
+    .with_context(|| format!("Failed to parse palette file: {:?}", path))?;
+
+    let colors = ColorConfig { stops, custom: true };
+    colors.validate()
+        .with_context(|| format!("Invalid palette in {:?}", path))?;
+    Ok(colors.stops)
+}
+
+fn parse_toml(content: &str) -> Result<Vec<ColorStop>> {
+    #[derive(Deserialize)]
+    struct PaletteFile {
+        stops: Vec<ColorStop>,
+    }
+
+    let file: PaletteFile = toml::from_str(content)?;
+    Ok(file.stops.into_iter().map(clamp_position).collect())
+}
+
+/// Rows of `position,color`; blank lines, `#` comments and a `position,color` header are skipped
+fn parse_csv(content: &str) -> Result<Vec<ColorStop>> {
+    let mut stops = Vec::new();
+    // Only the first row after any comments or blank lines may be a header
+    let mut seen_data = false;
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [position, color] = fields[..] else {
+            bail!("line {}: expected \"position,color\", found {:?}", i + 1, line);
+        };
+        let is_first_row = !std::mem::replace(&mut seen_data, true);
+        let position: f32 = match position.parse() {
+            Ok(p) => p,
+            Err(_) if is_first_row => continue, // Header row
+            Err(_) => bail!("line {}: invalid position {:?}", i + 1, position),
+        };
+
+        let color = if color.starts_with('#') { color.to_string() } else { format!("#{}", color) };
+        stops.push(clamp_position(ColorStop { position, color }));
+    }
+
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    Ok(stops)
+}
+
+/// GIMP gradient: a "GIMP Gradient" header, an optional "Name:" line, the segment count,
+/// then one line per segment:
+/// `left middle right  r g b a (left color)  r g b a (right color)  blend coloring ...`.
+/// Segments are converted to stops at their left, middle and right points (blending is
+/// approximated as linear).
+fn parse_ggr(content: &str) -> Result<Vec<ColorStop>> {
+    let mut lines = content.lines().map(str::trim).filter(|l| !l.is_empty());
+
+    if lines.next() != Some("GIMP Gradient") {
+        bail!("missing \"GIMP Gradient\" header");
+    }
+    let mut count_line = lines.next().context("missing segment count")?;
+    if count_line.starts_with("Name:") {
+        count_line = lines.next().context("missing segment count")?;
+    }
+    let count: usize = count_line.parse()
+        .with_context(|| format!("invalid segment count {:?}", count_line))?;
+
+    let mut stops: Vec<ColorStop> = Vec::new();
+    for i in 0..count {
+        let line = lines.next()
+            .with_context(|| format!("expected {} segments, found {}", count, i))?;
+        let values = line.split_whitespace()
+            .take(11)
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<f32>, _>>()
+            .with_context(|| format!("segment {}: invalid number in {:?}", i + 1, line))?;
+        if values.len() < 11 {
+            bail!("segment {}: expected at least 11 values, found {}", i + 1, values.len());
+        }
+
+        let (left, middle, right) = (values[0], values[1], values[2]);
+        let left_color = [values[3], values[4], values[5]];
+        let right_color = [values[7], values[8], values[9]];
+        let middle_color = [0, 1, 2].map(|c| (left_color[c] + right_color[c]) / 2.0);
+
+        push_stop(&mut stops, left, left_color);
+        if middle > left && middle < right {
+            push_stop(&mut stops, middle, middle_color);
+        }
+        push_stop(&mut stops, right, right_color);
+    }
+
+    Ok(stops)
+}
+
+/// Append a stop, merging it with the previous one if they coincide.
+/// A different color at the same position (a hard edge) is nudged just after it.
+fn push_stop(stops: &mut Vec<ColorStop>, position: f32, rgb: [f32; 3]) {
+    let color = format!(
+        "#{:02x}{:02x}{:02x}",
+        (rgb[0].clamp(0.0, 1.0) * 255.0).round() as u8,
+        (rgb[1].clamp(0.0, 1.0) * 255.0).round() as u8,
+        (rgb[2].clamp(0.0, 1.0) * 255.0).round() as u8,
+    );
+    let mut stop = clamp_position(ColorStop { position, color });
+
+    if let Some(prev) = stops.last() {
+        if stop.position <= prev.position {
+            if stop.color == prev.color {
+                return;
+            }
+            stop.position = prev.position + EDGE_OFFSET;
+        }
+    }
+    stops.push(stop);
+}
+
+fn clamp_position(stop: ColorStop) -> ColorStop {
+    ColorStop {
+        position: stop.position.clamp(0.0, 1.0),
+        color: stop.color,
+    }
+}
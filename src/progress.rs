@@ -0,0 +1,117 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Pipeline stage reported to progress callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Decode,
+    Stft,
+    Render,
+}
+
+impl Stage {
+    fn label(&self) -> &'static str {
+        match self {
+            Stage::Decode => "Decoding",
+            Stage::Stft => "STFT",
+            Stage::Render => "Rendering",
+        }
+    }
+
+    fn done_label(&self) -> &'static str {
+        match self {
+            Stage::Decode => "Decoded ✓",
+            Stage::Stft => "STFT ✓",
+            Stage::Render => "Render ✓",
+        }
+    }
+}
+
+/// Progress callback: receives the current stage and its completed fraction (0.0..=1.0).
+/// Called from worker threads during parallel stages, so it must be `Sync`.
+pub type ProgressCallback<'a> = dyn Fn(Stage, f32) + Sync + 'a;
+
+/// Invoke the callback if one was supplied
+pub fn report(progress: Option<&ProgressCallback>, stage: Stage, fraction: f32) {
+    if let Some(callback) = progress {
+        callback(stage, fraction.clamp(0.0, 1.0));
+    }
+}
+
+/// Set once cancellation has been requested (e.g. by Ctrl-C)
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Error returned by long-running stages that stopped because cancellation was requested
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Ask decoding, STFT and rendering to stop at their next check
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Fail with `Cancelled` if cancellation has been requested
+pub fn check_cancelled() -> Result<(), Cancelled> {
+    if is_cancelled() { Err(Cancelled) } else { Ok(()) }
+}
+
+/// Resolution of the terminal bars (fractions are mapped onto this many steps)
+const BAR_STEPS: u64 = 1000;
+
+/// Terminal progress bars (indicatif), one per stage, created on first report
+#[derive(Default)]
+pub struct TerminalProgress {
+    bars: Mutex<Vec<(Stage, ProgressBar)>>,
+}
+
+impl TerminalProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&self, stage: Stage, fraction: f32) {
+        let mut bars = self.bars.lock().unwrap();
+        let bar = match bars.iter().find(|(s, _)| *s == stage) {
+            Some((_, bar)) => bar.clone(),
+            None => {
+                let bar = new_bar(stage);
+                bars.push((stage, bar.clone()));
+                bar
+            }
+        };
+
+        if bar.is_finished() {
+            return;
+        }
+        bar.set_position((fraction * BAR_STEPS as f32) as u64);
+        if fraction >= 1.0 {
+            bar.finish_with_message(stage.done_label());
+        }
+    }
+}
+
+fn new_bar(stage: Stage) -> ProgressBar {
+    let pb = ProgressBar::new(BAR_STEPS);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} {msg} [{bar:40.cyan/blue}] {percent}% ({eta})")
+            .unwrap()
+            .progress_chars("━━╸")
+    );
+    pb.set_message(stage.label());
+    pb
+}
@@ -0,0 +1,98 @@
+use anyhow::{bail, Result, Context};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A target tonal-balance curve loaded from TOML:
+///
+/// ```toml
+/// name = "Mastering target"
+/// points = [
+///     { freq = 100.0, db = 0.0 },
+///     { freq = 1000.0, db = -6.0 },
+/// ]
+/// ```
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReferenceCurve {
+    pub name: Option<String>,
+    pub points: Vec<ReferencePoint>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct ReferencePoint {
+    pub freq: f32,
+    pub db: f32,
+}
+
+/// Measured level versus the reference at one point.
+/// Levels are aligned so that the mean deviation across all points is zero.
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct ReferenceDeviation {
+    pub freq_hz: f32,
+    pub reference_db: f32,
+    pub measured_db: f32,
+    pub deviation_db: f32,
+}
+
+impl ReferenceCurve {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read reference curve: {:?}", path))?;
+        let curve: ReferenceCurve = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse reference curve: {:?}", path))?;
+
+        if curve.points.is_empty() {
+            bail!("reference curve {:?} has no points", path);
+        }
+        if let Some(p) = curve.points.iter().find(|p| p.freq <= 0.0) {
+            bail!("reference curve {:?}: frequency {} must be positive", path, p.freq);
+        }
+
+        Ok(curve)
+    }
+}
+
+/// Compare the averaged spectrum against the reference curve.
+/// The measured level at each point is the mean power over a 1/3-octave band around it.
+/// Points above Nyquist, or at or above `max_freq` (the top of the analyzed spectrum), are skipped.
+pub fn compare_to_reference(
+    average_spectrum: &[f32],
+    sample_rate: u32,
+    max_freq: Option<f32>,
+    curve: &ReferenceCurve,
+) -> Vec<ReferenceDeviation> {
+    let nyquist = sample_rate as f32 / 2.0;
+    let num_bins = average_spectrum.len();
+    let norm_factor = num_bins as f32 / 2.0;
+    let band_edge = 2f32.powf(1.0 / 6.0); // half of a third-octave
+
+    let measured: Vec<(ReferencePoint, f32)> = curve.points.iter()
+        .filter(|p| p.freq < max_freq.unwrap_or(nyquist).min(nyquist))
+        .map(|&p| {
+            let to_bin = |f: f32| ((f / nyquist) * num_bins as f32).round() as usize;
+            let lo = to_bin(p.freq / band_edge).min(num_bins - 1);
+            let hi = to_bin(p.freq * band_edge).clamp(lo + 1, num_bins);
+
+            let band = &average_spectrum[lo..hi];
+            let mean_power = band.iter().map(|m| m * m).sum::<f32>() / band.len() as f32;
+            let db = 10.0 * (mean_power / (norm_factor * norm_factor) + 1e-18).log10();
+            (p, db)
+        })
+        .collect();
+
+    if measured.is_empty() {
+        return Vec::new();
+    }
+
+    // Align overall level so only the shape is compared
+    let offset = measured.iter().map(|(p, db)| db - p.db).sum::<f32>() / measured.len() as f32;
+
+    measured.into_iter()
+        .map(|(p, db)| ReferenceDeviation {
+            freq_hz: p.freq,
+            reference_db: p.db,
+            measured_db: db - offset,
+            deviation_db: db - offset - p.db,
+        })
+        .collect()
+}
@@ -1,22 +1,118 @@
 use anyhow::Result;
-use image::{RgbImage, Rgb};
-use imageproc::drawing::{draw_line_segment_mut, draw_text_mut, draw_filled_rect_mut};
+use image::imageops::FilterType;
+use image::{ImageBuffer, Pixel, RgbImage, Rgb};
+use imageproc::drawing::{draw_line_segment_mut, draw_text_mut, draw_filled_rect_mut, text_size};
 use imageproc::rect::Rect;
 use rusttype::{Font, Scale};
+use std::cell::RefCell;
 use std::process::Command;
 use std::path::PathBuf;
+use crate::analysis::TonalPeak;
 use crate::config::{Config, ColorStop};
+use crate::spectrogram::{apply_color_curve, display_range, DB_RANGE};
+use crate::{AmplitudeScale, ColorCurve, FreqUnit, RolloffLabel};
 
 /// Layout constants
 const LEGEND_WIDTH: u32 = 60;       // Width of color bar on right
 const LEGEND_PADDING: u32 = 10;      // Padding around legend
 const LABEL_MARGIN: i32 = 50;        // Margin to avoid label overlap
+const CORNER_LABEL_SPACE: u32 = 18;  // Top/bottom margin that fits the small corner labels
+
+/// Blank space around the finished image (spectrogram and color bar), in pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Margins {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Margins {
+    fn scaled(self, factor: u32) -> Self {
+        Margins {
+            top: self.top * factor,
+            right: self.right * factor,
+            bottom: self.bottom * factor,
+            left: self.left * factor,
+        }
+    }
+}
+
+/// Overlay color plus the supersampling factor that every overlay size is multiplied by
+#[derive(Clone, Copy)]
+struct Pen {
+    color: Rgb<u8>,
+    scale: u32,
+}
+
+impl Pen {
+    /// `v` output pixels in pixels of the (supersampled) drawing
+    fn px(&self, v: f32) -> f32 {
+        v * self.scale as f32
+    }
+
+    /// Integer `px`, for text positions
+    fn pxi(&self, v: i32) -> i32 {
+        v * self.scale as i32
+    }
+
+    /// A line `scale` pixels thick, so it keeps its weight once downsampled
+    fn line(&self, img: &mut RgbImage, from: (f32, f32), to: (f32, f32)) {
+        let steep = (to.1 - from.1).abs() > (to.0 - from.0).abs();
+        for i in 0..self.scale {
+            let offset = (i as i32 - (self.scale as i32 - 1) / 2) as f32;
+            let (dx, dy) = if steep { (offset, 0.0) } else { (0.0, offset) };
+            draw_line_segment_mut(img, (from.0 + dx, from.1 + dy), (to.0 + dx, to.1 + dy), self.color);
+        }
+    }
+}
+
+/// Shrink an image rendered at `factor` times its size back down with a Lanczos filter,
+/// anti-aliasing its lines and text (see `RenderOptions::supersample`)
+pub fn downsample<P>(img: ImageBuffer<P, Vec<P::Subpixel>>, factor: u32) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+{
+    if factor <= 1 {
+        return img;
+    }
+    image::imageops::resize(&img, img.width() / factor, img.height() / factor, FilterType::Lanczos3)
+}
 
 /// Rendering options for the final image
+#[derive(Clone)]
 pub struct RenderOptions {
     pub linear: bool,
     pub show_rolloff: bool,
     pub rolloff_frequencies: Option<Vec<f32>>, // Hz per time frame
+    pub rolloff_label: RolloffLabel, // Value printed at the right end of the rolloff line
+    pub centroid_frequencies: Option<Vec<f32>>, // Hz per time frame
+    pub cutoff_frequencies: Option<Vec<f32>>, // Hz per time frame
+    pub overlay_frequencies: Vec<Vec<f32>>, // Lines loaded from another analysis' series CSV, Hz per time frame
+    pub amplitude: AmplitudeScale,
+    pub color_curve: ColorCurve, // Moves the color bar labels to where their levels land
+    pub show_octaves: bool,
+    pub time_grid: Option<f64>, // Seconds between vertical grid lines
+    pub time_ticks: Option<f64>, // Seconds between time axis labels (auto if None)
+    pub time_offset_secs: f64, // Time at the left edge (nonzero for a window into a longer file)
+    pub a_weighted: bool,
+    pub tilt_db_per_octave: Option<f32>, // Display tilt, noted under the scale label
+    pub freq_unit: FreqUnit,
+    pub harmonic_frequencies: Option<Vec<f32>>, // Fundamental first, then 2f, 3f, ...
+    pub tonal_peaks: Option<Vec<TonalPeak>>, // Loudest first, labelled with frequency and level
+    pub freq_min: Option<f32>, // Lower edge of the displayed slice (Hz)
+    pub freq_max: Option<f32>, // Upper edge of the displayed slice (Hz)
+    pub text_outline: u32, // Outline thickness around labels in pixels (0 = none)
+    pub palette_name: String, // Caption under the color bar ("custom" for config stops)
+    pub legend_ticks: u32, // Number of evenly spaced color bar labels (at least 2)
+    pub show_legend: bool, // Draw the color bar to the right of the spectrogram
+    pub phase: bool, // Color bar shows phase (degrees) instead of level
+    pub noise_floor_level: Option<f32>, // Color scale position of the noise floor, marked on the color bar
+    pub scale_top_db: Option<f32>, // Fixed level at the top of the color scale (dBFS), labelled absolutely
+    pub margins: Margins, // Space around the image that edge labels can extend into
+    /// The spectrogram was rendered at this many times the output size (1, 2 or 4); overlays are
+    /// drawn at the same scale and the result is downsampled to the output size
+    pub supersample: u32,
 }
 
 /// Prepare the final image with overlays and optional color bar
@@ -28,16 +124,19 @@ pub fn prepare_final_image(
     options: RenderOptions,
 ) -> Result<RgbImage> {
     let font = load_font(config)?;
+    let ss = options.supersample.max(1);
+    let margins = options.margins.scaled(ss);
     let font = match font {
         Some(f) => f,
-        None => return Ok(spectrogram),
+        None => return Ok(downsample(add_margins(&spectrogram, margins), ss)),
     };
 
     let spec_width = spectrogram.width();
     let spec_height = spectrogram.height();
     
     // Create wider image to accommodate color bar on the right
-    let total_width = spec_width + LEGEND_WIDTH + LEGEND_PADDING;
+    let legend_width = if options.show_legend { (LEGEND_WIDTH + LEGEND_PADDING) * ss } else { 0 };
+    let total_width = spec_width + legend_width;
     let mut img = RgbImage::from_pixel(total_width, spec_height, Rgb([0, 0, 0]));
     
     // Copy spectrogram to left portion
@@ -47,231 +146,782 @@ pub fn prepare_final_image(
         }
     }
 
-    let font_size = 20.0;
-    let small_font_size = 14.0;
+    let pen = |color| Pen { color, scale: ss };
+    let font_size = 20.0 * ss as f32;
+    let small_font_size = 14.0 * ss as f32;
     let scale = Scale { x: font_size, y: font_size };
     let small_scale = Scale { x: small_font_size, y: small_font_size };
-    let text_color = Rgb([255, 255, 255]);
-    let outline_color = Rgb([0, 0, 0]);
-    let line_color = Rgb([200, 200, 200]); 
-    let rolloff_color = Rgb([255, 200, 50]); // Yellow/orange for rolloff line
+    let line_pen = pen(Rgb([200, 200, 200]));
+    let rolloff_pen = pen(Rgb([255, 200, 50])); // Yellow/orange for rolloff line
+    let cutoff_pen = pen(Rgb([80, 220, 255])); // Cyan for threshold cutoff line
+    let centroid_pen = pen(Rgb([120, 255, 120])); // Green for spectral centroid line
+    let harmonic_pen = pen(Rgb([255, 90, 200])); // Magenta for harmonic markers
+    let peak_pen = pen(Rgb([255, 255, 255])); // White for tonal peak markers
+    let overlay_pen = pen(Rgb([255, 70, 70])); // Red for series loaded from another analysis
     
-    // Helper to draw outlined text
-    let draw_outlined_text = |img: &mut RgbImage, text: &str, x: i32, y: i32, s: Scale| {
-        for ox in -1..=1 {
-            for oy in -1..=1 {
-                if ox != 0 || oy != 0 {
-                    draw_text_mut(img, outline_color, x + ox, y + oy, s, &font, text);
-                }
-            }
-        }
-        draw_text_mut(img, text_color, x, y, s, &font, text);
+    // Labels are collected and drawn last, onto the image with its margins, so text near an
+    // edge runs into the margin instead of being cut off. The image argument is the one the
+    // overlays are drawn on; it only keeps call sites uniform.
+    let labels: RefCell<Vec<(String, i32, i32, Scale)>> = RefCell::new(Vec::new());
+    let draw_outlined_text = |_: &mut RgbImage, text: &str, x: i32, y: i32, s: Scale| {
+        labels.borrow_mut().push((text.to_string(), x, y, s));
     };
 
-    let nyquist = sample_rate as f32 / 2.0;
+    // Frequency span (Hz) covered by the spectrogram height
+    let range = display_range(sample_rate, options.linear, options.freq_min, options.freq_max);
+
+    // Shade alternating octaves underneath all other overlays
+    if options.show_octaves && !options.linear {
+        draw_octave_bands(&mut img, spec_width, spec_height, range);
+    }
+
+    // Faint vertical timecode grid, also underneath labels
+    if let (Some(step), Some(duration_secs)) = (options.time_grid, known_duration(duration_secs)) {
+        draw_time_grid(&mut img, options.time_offset_secs, duration_secs, spec_width, spec_height, step, line_pen);
+    }
 
     // Draw frequency axis labels
     draw_frequency_axis(
         &mut img, 
-        sample_rate, 
+        range, 
         options.linear, 
         spec_height, 
-        line_color, 
+        options.freq_unit,
+        line_pen, 
         &|img, text, x, y| draw_outlined_text(img, text, x, y, scale)
     );
 
-    // Draw time axis labels
-    draw_time_axis(
-        &mut img, 
-        duration_secs, 
-        spec_width, 
-        spec_height, 
-        line_color, 
-        &|img, text, x, y| draw_outlined_text(img, text, x, y, scale)
-    );
+    // Draw time axis labels, unless the duration is zero or unknown
+    if let Some(duration_secs) = known_duration(duration_secs) {
+        draw_time_axis(
+            &mut img, 
+            (options.time_offset_secs, duration_secs),
+            spec_width, 
+            spec_height, 
+            options.time_ticks.or(options.time_grid),
+            line_pen, 
+            &|img, text, x, y| draw_outlined_text(img, text, x, y, scale)
+        );
+    }
 
-    // Draw axis title labels (small, subtle)
+    // Draw axis title labels (small, subtle), moved out into the top and bottom margins
+    // when they're tall enough so they can't collide with the axis labels
+    let corner_y = if margins.top >= CORNER_LABEL_SPACE * ss {
+        (margins.top as i32 - small_font_size as i32) / 2 - margins.top as i32
+    } else {
+        line_pen.pxi(5)
+    };
     // "Hz" near top-left corner
-    draw_outlined_text(&mut img, "Hz", 5, 5, small_scale);
+    draw_outlined_text(&mut img, "Hz", line_pen.pxi(5), corner_y, small_scale);
     
     // "Time" near bottom-right of spectrogram area
-    let time_label_x = (spec_width as i32) - 40;
-    let time_label_y = (spec_height as i32) - 18;
+    let time_label_x = (spec_width as i32) - line_pen.pxi(40);
+    let time_label_y = if margins.bottom >= CORNER_LABEL_SPACE * ss {
+        spec_height as i32 + (margins.bottom as i32 - small_font_size as i32) / 2
+    } else {
+        (spec_height as i32) - line_pen.pxi(18)
+    };
     draw_outlined_text(&mut img, "Time", time_label_x, time_label_y, small_scale);
 
     // Draw scale type indicator (top-right corner of spectrogram)
     let scale_label = if options.linear { "LINEAR" } else { "LOG" };
-    let scale_x = (spec_width as i32) - 55;
-    draw_outlined_text(&mut img, scale_label, scale_x, 5, small_scale);
+    let scale_x = (spec_width as i32) - line_pen.pxi(55);
+    draw_outlined_text(&mut img, scale_label, scale_x, corner_y, small_scale);
+    // Display weightings stack under the scale label, right-aligned
+    let weightings = [
+        options.a_weighted.then(|| "A-WEIGHTED".to_string()),
+        options.tilt_db_per_octave.map(|db| format!("TILT {:+}dB/OCT", db)),
+    ];
+    for (i, label) in weightings.into_iter().flatten().enumerate() {
+        let (text_width, _) = text_size(small_scale, &font, &label);
+        draw_outlined_text(&mut img, &label, spec_width as i32 - text_width - line_pen.pxi(5), corner_y + line_pen.pxi(17) * (i as i32 + 1), small_scale);
+    }
+
+    // Draw series from another analysis under this file's own lines
+    for overlay_freqs in &options.overlay_frequencies {
+        draw_frequency_line(
+            &mut img,
+            overlay_freqs,
+            spec_width,
+            spec_height,
+            range,
+            options.linear,
+            overlay_pen
+        );
+    }
 
     // Draw spectral rolloff line if enabled
     if options.show_rolloff {
         if let Some(ref rolloff_freqs) = options.rolloff_frequencies {
-            draw_rolloff_line(
+            draw_frequency_line(
                 &mut img, 
                 rolloff_freqs, 
                 spec_width, 
                 spec_height, 
-                nyquist, 
+                range, 
                 options.linear, 
-                rolloff_color
+                rolloff_pen
             );
+
+            if let Some(freq) = rolloff_label_value(rolloff_freqs, options.rolloff_label) {
+                // Not every font has "≈"
+                let approx = if font.glyph('≈').id().0 != 0 { "≈" } else { "~" };
+                let label = format!("{}{}", approx, format_freq_value(freq, options.freq_unit));
+                let (text_width, _) = text_size(small_scale, &font, &label);
+                let y = freq_to_y(freq, range, spec_height, options.linear) as i32;
+                draw_outlined_text(&mut img, &label, spec_width as i32 - text_width - rolloff_pen.pxi(8), (y - rolloff_pen.pxi(18)).max(0), small_scale);
+            }
+        }
+    }
+
+    // Draw spectral centroid (brightness) line if requested
+    if let Some(ref centroid_freqs) = options.centroid_frequencies {
+        draw_frequency_line(
+            &mut img,
+            centroid_freqs,
+            spec_width,
+            spec_height,
+            range,
+            options.linear,
+            centroid_pen
+        );
+    }
+
+    // Draw threshold cutoff line if requested
+    if let Some(ref cutoff_freqs) = options.cutoff_frequencies {
+        draw_frequency_line(
+            &mut img,
+            cutoff_freqs,
+            spec_width,
+            spec_height,
+            range,
+            options.linear,
+            cutoff_pen
+        );
+    }
+
+    // Mark the fundamental and its harmonics
+    if let Some(ref harmonic_freqs) = options.harmonic_frequencies {
+        let positions: Vec<f32> = harmonic_freqs.iter()
+            .map(|&f| freq_to_y(f, range, spec_height, options.linear))
+            .collect();
+        draw_harmonic_markers(
+            &mut img,
+            &positions,
+            spec_width,
+            spec_height,
+            harmonic_pen,
+            &|img, text, x, y| draw_outlined_text(img, text, x, y, small_scale)
+        );
+    }
+
+    // Tick and label each tonal peak at the right edge; labels of close peaks are
+    // stacked downwards so they don't overlap
+    if let Some(ref peaks) = options.tonal_peaks {
+        let mut marks: Vec<(f32, &TonalPeak)> = peaks.iter()
+            .map(|peak| (freq_to_y(peak.freq_hz, range, spec_height, options.linear), peak))
+            .filter(|&(y, _)| y >= 0.0 && y < spec_height as f32)
+            .collect();
+        marks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let tick_start = spec_width.saturating_sub(16 * ss) as f32;
+        let mut next_label_y = i32::MIN;
+        for (y, peak) in marks {
+            peak_pen.line(&mut img, (tick_start, y), (spec_width as f32, y));
+            let label = format!("{:.1}Hz {:.1}dB", peak.freq_hz, peak.level_db);
+            let (text_width, text_height) = text_size(small_scale, &font, &label);
+            let label_y = (y as i32 - peak_pen.pxi(8)).max(next_label_y);
+            draw_outlined_text(&mut img, &label, tick_start as i32 - text_width - peak_pen.pxi(4), label_y, small_scale);
+            next_label_y = label_y + text_height + peak_pen.pxi(3);
         }
     }
 
     // Draw color bar / legend on the right side
-    draw_color_bar(
-        &mut img,
-        &config.colors.stops,
-        spec_width,
-        spec_height,
-        &|img, text, x, y| draw_outlined_text(img, text, x, y, small_scale)
+    if options.show_legend {
+        draw_color_bar(
+            &mut img,
+            &config.colors.stops,
+            spec_width,
+            spec_height,
+            &options,
+            &|img, text, x, y| draw_outlined_text(img, text, x, y, small_scale)
+        );
+    }
+
+    let mut framed = add_margins(&img, margins);
+    let draw_label = outlined_text_drawer(&font, options.text_outline * ss);
+    for (text, x, y, s) in labels.into_inner() {
+        draw_label(&mut framed, &text, x + margins.left as i32, y + margins.top as i32, s);
+    }
+
+    Ok(downsample(framed, ss))
+}
+
+/// `img` inside a black border of `margins`
+fn add_margins(img: &RgbImage, margins: Margins) -> RgbImage {
+    let mut framed = RgbImage::from_pixel(
+        img.width() + margins.left + margins.right,
+        img.height() + margins.top + margins.bottom,
+        Rgb([0, 0, 0]),
+    );
+    image::imageops::replace(&mut framed, img, margins.left as i64, margins.top as i64);
+    framed
+}
+
+/// Widen a finished 8-bit image to 16 bits per channel. Spectrogram pixels (the
+/// `deep_spectrogram` area inside `margins`) that no overlay touched take their 16-bit color
+/// instead, so axes, labels and lines stay as drawn while the gradient itself loses its banding.
+pub fn deepen_image(
+    img: &RgbImage,
+    spectrogram: &RgbImage,
+    deep_spectrogram: &ImageBuffer<Rgb<u16>, Vec<u16>>,
+    margins: Margins,
+) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let pixel = *img.get_pixel(x, y);
+        let (sx, sy) = (x.wrapping_sub(margins.left), y.wrapping_sub(margins.top));
+        let untouched = sx < spectrogram.width()
+            && sy < spectrogram.height()
+            && *spectrogram.get_pixel(sx, sy) == pixel;
+        if untouched {
+            *deep_spectrogram.get_pixel(sx, sy)
+        } else {
+            Rgb(pixel.0.map(|v| v as u16 * 257))
+        }
+    })
+}
+
+/// White text with a dark outline of `outline` pixels, readable over any palette
+fn outlined_text_drawer<'a>(font: &'a Font<'static>, outline: u32) -> impl Fn(&mut RgbImage, &str, i32, i32, Scale) + 'a {
+    let text_color = Rgb([255, 255, 255]);
+    let outline_color = Rgb([0, 0, 0]);
+    let outline = outline as i32;
+
+    move |img: &mut RgbImage, text: &str, x: i32, y: i32, s: Scale| {
+        for ox in -outline..=outline {
+            for oy in -outline..=outline {
+                if ox != 0 || oy != 0 {
+                    draw_text_mut(img, outline_color, x + ox, y + oy, s, font, text);
+                }
+            }
+        }
+        draw_text_mut(img, text_color, x, y, s, font, text);
+    }
+}
+
+/// Render a palette as a horizontal gradient, low values on the left, for documentation.
+/// With a `caption`, the name is written in a band under the gradient (if a font is available).
+pub fn render_palette_swatch(
+    stops: &[ColorStop],
+    caption: Option<&str>,
+    width: u32,
+    height: u32,
+    config: &Config,
+) -> Result<RgbImage> {
+    const CAPTION_HEIGHT: u32 = 20;
+
+    let gradient = create_gradient_map(stops, width as usize);
+    let swatch = RgbImage::from_fn(width, height, |x, _| gradient[x as usize]);
+
+    let (caption, font) = match (caption, load_font(config)?) {
+        (Some(caption), Some(font)) => (caption, font),
+        _ => return Ok(swatch),
+    };
+    let mut img = RgbImage::from_pixel(width, height + CAPTION_HEIGHT, Rgb([0, 0, 0]));
+    image::imageops::replace(&mut img, &swatch, 0, 0);
+    let draw_outlined_text = outlined_text_drawer(&font, 1);
+    draw_outlined_text(&mut img, caption, 4, height as i32 + 3, Scale { x: 14.0, y: 14.0 });
+
+    Ok(img)
+}
+
+/// Add a frequency axis under a barcode strip (see `spectrogram::render_barcode`)
+pub fn prepare_barcode_image(
+    strip: RgbImage,
+    sample_rate: u32,
+    config: &Config,
+    options: &RenderOptions,
+) -> Result<RgbImage> {
+    const AXIS_HEIGHT: u32 = 20;
+
+    let font = match load_font(config)? {
+        Some(f) => f,
+        None => return Ok(strip),
+    };
+    let (width, strip_height) = strip.dimensions();
+    let mut img = RgbImage::from_pixel(width, strip_height + AXIS_HEIGHT, Rgb([0, 0, 0]));
+    image::imageops::replace(&mut img, &strip, 0, 0);
+
+    let small_scale = Scale { x: 14.0, y: 14.0 };
+    let line_color = Rgb([200, 200, 200]);
+    let draw_outlined_text = outlined_text_drawer(&font, options.text_outline);
+
+    let range = display_range(sample_rate, options.linear, options.freq_min, options.freq_max);
+    let ticks = if options.linear { linear_freq_ticks(range) } else { log_freq_ticks(range) };
+    for freq in ticks {
+        // freq_to_y measures from the top; flip it to get the distance from the left edge
+        let x = (width - 1) as f32 - freq_to_y(freq, range, width, options.linear);
+        let label = format_freq_label(freq, options.freq_unit);
+        // Keep labels clear of the right edge
+        if x < 0.0 || x > width as f32 - 8.0 * label.len() as f32 {
+            continue;
+        }
+        draw_line_segment_mut(&mut img, (x, strip_height as f32), (x, strip_height as f32 + 4.0), line_color);
+        draw_outlined_text(&mut img, &label, x as i32 + 2, strip_height as i32 + 3, small_scale);
+    }
+
+    Ok(img)
+}
+
+/// Plot the time-averaged spectrum as a line: frequency across (log or linear, over the
+/// display range) and level from 0 dBFS at the top down to `-DB_RANGE`, on a labelled grid.
+/// Each column shows the loudest bin it covers, so narrow peaks survive downsampling.
+pub fn render_spectrum_plot(
+    average_spectrum: &[f32],
+    sample_rate: u32,
+    width: u32,
+    height: u32,
+    config: &Config,
+    options: &RenderOptions,
+) -> Result<RgbImage> {
+    const MARGIN_LEFT: u32 = 44;
+    const MARGIN_RIGHT: u32 = 16;
+    const MARGIN_TOP: u32 = 24;
+    const MARGIN_BOTTOM: u32 = 22;
+    const DB_STEP: f32 = 10.0;
+
+    let plot_width = width.saturating_sub(MARGIN_LEFT + MARGIN_RIGHT).max(1);
+    let plot_height = height.saturating_sub(MARGIN_TOP + MARGIN_BOTTOM).max(1);
+    let mut img = RgbImage::from_pixel(
+        plot_width + MARGIN_LEFT + MARGIN_RIGHT,
+        plot_height + MARGIN_TOP + MARGIN_BOTTOM,
+        Rgb([0, 0, 0]),
     );
+    let (left, top) = (MARGIN_LEFT as f32, MARGIN_TOP as f32);
+    let (right, bottom) = (left + plot_width as f32, top + plot_height as f32);
+
+    let grid_color = Rgb([50, 50, 50]);
+    let line_color = Rgb([200, 200, 200]);
+    let trace_color = Rgb([255, 210, 60]);
+    let small_scale = Scale { x: 14.0, y: 14.0 };
+    let font = load_font(config)?;
+    let draw_outlined_text = font.as_ref().map(|f| outlined_text_drawer(f, options.text_outline));
+
+    // Level grid, labelled in dBFS on the left
+    let db_to_y = |db: f32| top + (-db / DB_RANGE).clamp(0.0, 1.0) * plot_height as f32;
+    for step in 0..=(DB_RANGE / DB_STEP) as i32 {
+        let db = -step as f32 * DB_STEP;
+        let y = db_to_y(db);
+        draw_line_segment_mut(&mut img, (left, y), (right, y), grid_color);
+        if let (Some(draw), Some(font)) = (&draw_outlined_text, &font) {
+            let label = format!("{}", db);
+            let (label_width, _) = text_size(small_scale, font, &label);
+            draw(&mut img, &label, MARGIN_LEFT as i32 - label_width - 6, y as i32 - 7, small_scale);
+        }
+    }
+
+    // Frequency grid, labelled under the plot
+    let range = display_range(sample_rate, options.linear, options.freq_min, options.freq_max);
+    let freq_to_x = |freq: f32| right - 1.0 - freq_to_y(freq, range, plot_width, options.linear);
+    let ticks = if options.linear { linear_freq_ticks(range) } else { log_freq_ticks(range) };
+    for freq in ticks {
+        let x = freq_to_x(freq);
+        if !(left..=right).contains(&x) {
+            continue;
+        }
+        draw_line_segment_mut(&mut img, (x, top), (x, bottom), grid_color);
+        draw_line_segment_mut(&mut img, (x, bottom), (x, bottom + 4.0), line_color);
+        if let Some(ref draw) = draw_outlined_text {
+            let label = format_freq_label(freq, options.freq_unit);
+            draw(&mut img, &label, x as i32 - 4 * label.len() as i32, bottom as i32 + 5, small_scale);
+        }
+    }
+    draw_line_segment_mut(&mut img, (left, top), (left, bottom), line_color);
+    draw_line_segment_mut(&mut img, (left, bottom), (right, bottom), line_color);
+    if let Some(ref draw) = draw_outlined_text {
+        draw(&mut img, "dBFS", 2, 2, small_scale);
+    }
+
+    // Trace: column x covers frequencies from x_to_freq(x) to x_to_freq(x + 1)
+    let num_bins = average_spectrum.len();
+    let nyquist = sample_rate as f32 / 2.0;
+    let full_scale = num_bins as f32 / 2.0;
+    let (min_freq, max_freq) = range;
+    let x_to_bin = |x: u32| {
+        let ratio = x as f32 / plot_width as f32;
+        let freq = if options.linear {
+            min_freq + ratio * (max_freq - min_freq)
+        } else {
+            min_freq * (max_freq / min_freq).powf(ratio)
+        };
+        (freq / nyquist * num_bins as f32).clamp(0.0, (num_bins - 1) as f32)
+    };
+    let mut previous: Option<(f32, f32)> = None;
+    for x in 0..plot_width {
+        let (start, end) = (x_to_bin(x), x_to_bin(x + 1));
+        let (first, last) = (start.ceil() as usize, end.floor() as usize);
+        let mag = if first <= last {
+            average_spectrum[first..=last].iter().fold(0.0f32, |max, &v| max.max(v))
+        } else {
+            // Zoomed in past one bin per column: interpolate between neighbours
+            let b0 = start.floor() as usize;
+            let b1 = (b0 + 1).min(num_bins - 1);
+            let fract = start - b0 as f32;
+            average_spectrum[b0] * (1.0 - fract) + average_spectrum[b1] * fract
+        };
+        let point = (left + x as f32, db_to_y(20.0 * (mag / full_scale + 1e-9).log10()));
+        if let Some(prev) = previous {
+            draw_line_segment_mut(&mut img, prev, point, trace_color);
+        }
+        previous = Some(point);
+    }
 
     Ok(img)
 }
 
 fn draw_frequency_axis<F>(
     img: &mut RgbImage,
-    sample_rate: u32,
+    range: (f32, f32),
     linear: bool,
     height: u32,
-    line_color: Rgb<u8>,
+    unit: FreqUnit,
+    pen: Pen,
     draw_text: &F,
 ) where F: Fn(&mut RgbImage, &str, i32, i32) {
-    let nyquist = sample_rate as f32 / 2.0;
     let height_i = height as i32;
+    let ticks = if linear { linear_freq_ticks(range) } else { log_freq_ticks(range) };
 
-    if linear {
-        let step_khz = 5.0;
-        let mut freq = 0.0;
-        
-        while freq <= nyquist / 1000.0 {
-            let y_ratio = freq * 1000.0 / nyquist;
-            let y_pos = (height as f32 * (1.0 - y_ratio)) as i32;
-            
-            // Skip if too close to bottom edge (overlap zone)
-            if y_pos >= 0 && y_pos < height_i && y_pos < height_i - LABEL_MARGIN {
-                draw_line_segment_mut(img, (0.0, y_pos as f32), (10.0, y_pos as f32), line_color);
-                let label = format!("{}k", freq as i32);
-                draw_text(img, &label, 15, y_pos - 10);
-            }
-            freq += step_khz;
+    for freq in ticks {
+        let y_pos = freq_to_y(freq, range, height, linear) as i32;
+
+        // Skip if too close to bottom edge (overlap zone)
+        if y_pos >= 0 && y_pos < height_i && y_pos < height_i - pen.pxi(LABEL_MARGIN) {
+            pen.line(img, (0.0, y_pos as f32), (pen.px(10.0), y_pos as f32));
+            let label = format_freq_label(freq, unit);
+            draw_text(img, &label, pen.pxi(15), y_pos - pen.pxi(10));
         }
-    } else {
-        let freqs = [50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0];
-        let min_freq = 20.0;
-        let max_freq = nyquist;
+    }
+}
 
-        for &freq in freqs.iter() {
-            if freq > max_freq { break; }
-            
-            let y_ratio = (freq / min_freq).log10() / (max_freq / min_freq).log10();
-            let y_pos = (height as f32 - 1.0 - (y_ratio * height as f32)) as i32;
-
-            // Skip if too close to bottom edge (overlap zone)
-            if y_pos >= 0 && y_pos < height_i && y_pos < height_i - LABEL_MARGIN {
-                draw_line_segment_mut(img, (0.0, y_pos as f32), (10.0, y_pos as f32), line_color);
-                
-                let label = if freq >= 1000.0 {
-                    format!("{}k", freq / 1000.0)
-                } else {
-                    format!("{}", freq as i32)
-                };
-                
-                draw_text(img, &label, 15, y_pos - 10);
-            }
+/// 1-2-5 frequencies (Hz) inside the range for a log axis.
+/// A narrow slice may contain fewer than two of them; fall back to evenly spaced ones.
+fn log_freq_ticks(range: (f32, f32)) -> Vec<f32> {
+    const LOG_TICKS: [f32; 9] = [50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0];
+
+    let ticks: Vec<f32> = LOG_TICKS.iter()
+        .copied()
+        .filter(|f| (range.0..=range.1).contains(f))
+        .collect();
+    if ticks.len() >= 2 { ticks } else { linear_freq_ticks(range) }
+}
+
+/// Evenly spaced frequencies (Hz) at a "nice" step giving at most 10 ticks across the range
+fn linear_freq_ticks(range: (f32, f32)) -> Vec<f32> {
+    const NICE_STEPS: [f32; 10] = [10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0];
+    const MAX_TICKS: f32 = 10.0;
+
+    let (low, high) = range;
+    let step = NICE_STEPS.iter()
+        .copied()
+        .find(|step| (high - low) / step <= MAX_TICKS)
+        .unwrap_or(NICE_STEPS[NICE_STEPS.len() - 1]);
+
+    // Multiply rather than accumulate so labels stay exact
+    let first = (low / step).ceil() as u32;
+    let last = (high / step).floor() as u32;
+    (first..=last).map(|k| k as f32 * step).collect()
+}
+
+/// The rolloff frequency to print for `label`, ignoring silent (0 Hz) columns
+fn rolloff_label_value(freqs: &[f32], label: RolloffLabel) -> Option<f32> {
+    let mut voiced: Vec<f32> = freqs.iter().copied().filter(|&f| f > 0.0).collect();
+    match label {
+        RolloffLabel::Off => None,
+        RolloffLabel::Final => voiced.last().copied(),
+        RolloffLabel::Median => {
+            voiced.sort_by(|a, b| a.total_cmp(b));
+            voiced.get(voiced.len() / 2).copied()
         }
     }
 }
 
+/// Format a measured frequency with one decimal in kHz ("16.2k") or whole Hz ("850")
+fn format_freq_value(freq: f32, unit: FreqUnit) -> String {
+    let use_khz = match unit {
+        FreqUnit::Auto => freq >= 1000.0,
+        FreqUnit::Hz => false,
+        FreqUnit::Khz => true,
+    };
+
+    if use_khz {
+        format!("{:.1}k", freq / 1000.0)
+    } else {
+        format!("{:.0}", freq)
+    }
+}
+
+/// Format a frequency axis label in the requested unit ("500", "2k", "0.5k", "2000")
+fn format_freq_label(freq: f32, unit: FreqUnit) -> String {
+    let use_khz = match unit {
+        FreqUnit::Auto => freq >= 1000.0,
+        FreqUnit::Hz => false,
+        FreqUnit::Khz => true,
+    };
+
+    if use_khz {
+        format!("{}k", freq / 1000.0)
+    } else {
+        format!("{}", freq.round() as i32)
+    }
+}
+
+/// Ticks and labels for the `(start, duration)` span in seconds shown across `width`
 fn draw_time_axis<F>(
     img: &mut RgbImage,
-    duration_secs: f64,
+    (start_secs, duration_secs): (f64, f64),
     width: u32,
     height: u32,
-    line_color: Rgb<u8>,
+    step_override: Option<f64>,
+    pen: Pen,
     draw_text: &F,
 ) where F: Fn(&mut RgbImage, &str, i32, i32) {
-    let step_secs = if duration_secs < 60.0 { 10.0 } else { 30.0 };
+    let px_per_sec = (width / pen.scale) as f64 / duration_secs.max(f64::EPSILON);
+    let step_secs = step_override.unwrap_or_else(|| auto_time_step(duration_secs, width / pen.scale));
+    // A step too small for labels MIN_TICK_SPACING apart keeps its ticks but labels only every
+    // `label_every`-th; ticks closer than MIN_MARK_SPACING are thinned out the same way
+    let px_per_step = step_secs * px_per_sec;
+    let label_every = (MIN_TICK_SPACING / px_per_step).ceil().max(1.0) as u64;
+    let (mark_step, label_every) = if px_per_step >= MIN_MARK_SPACING {
+        (step_secs, label_every)
+    } else {
+        (step_secs * label_every as f64, 1)
+    };
     let width_i = width as i32;
     let height_f = height as f32;
-    let mut t = 0.0;
+    let mut tick = (start_secs / mark_step).ceil() as u64;
     
-    while t <= duration_secs {
-        let x_ratio = t / duration_secs;
+    loop {
+        // Multiply rather than accumulate so fractional steps don't drift
+        let t = tick as f64 * mark_step;
+        if t > start_secs + duration_secs {
+            break;
+        }
+        let x_ratio = (t - start_secs) / duration_secs;
         let x_pos = (width as f32 * x_ratio as f32) as i32;
         
         if x_pos >= 0 && x_pos < width_i {
-            draw_line_segment_mut(
+            pen.line(
                 img, 
                 (x_pos as f32, height_f), 
-                (x_pos as f32, height_f - 10.0), 
-                line_color
+                (x_pos as f32, height_f - pen.px(10.0)), 
             );
             
-            let minutes = (t / 60.0).floor() as i32;
-            let seconds = (t % 60.0) as i32;
-            let label = format!("{}:{:02}", minutes, seconds);
-            
-            // Offset first label to the right, others centered around tick
-            let text_x = if t == 0.0 { x_pos + 5 } else { x_pos - 15 };
-            draw_text(img, &label, text_x, height as i32 - 28);
+            if tick.is_multiple_of(label_every) {
+                let label = format_time_label(t, step_secs);
+
+                // Offset a label at the left edge to the right, others centered around tick
+                let text_x = if x_pos < pen.pxi(15) { x_pos + pen.pxi(5) } else { x_pos - pen.pxi(15) };
+                draw_text(img, &label, text_x, height as i32 - pen.pxi(28));
+            }
+        }
+        tick += 1;
+    }
+}
+
+/// Duration usable for placing time ticks: positive and finite. A zero or unknown (NaN or
+/// infinite) duration, e.g. from a stream whose length isn't known, leaves the time axis bare
+/// rather than dividing by it.
+fn known_duration(duration_secs: f64) -> Option<f64> {
+    (duration_secs.is_finite() && duration_secs > 0.0).then_some(duration_secs)
+}
+
+/// Smallest gap between time axis labels, in output pixels
+const MIN_TICK_SPACING: f64 = 100.0;
+
+/// Smallest gap between unlabeled time axis ticks, in output pixels
+const MIN_MARK_SPACING: f64 = 5.0;
+
+/// Pick a "nice" tick interval so labels are at least `MIN_TICK_SPACING` pixels apart
+fn auto_time_step(duration_secs: f64, width: u32) -> f64 {
+    const NICE_STEPS: [f64; 16] = [
+        0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 30.0,
+        60.0, 120.0, 300.0, 600.0, 900.0, 1800.0, 3600.0,
+    ];
+
+    let px_per_sec = width as f64 / duration_secs.max(f64::EPSILON);
+    NICE_STEPS.iter()
+        .copied()
+        .find(|step| step * px_per_sec >= MIN_TICK_SPACING)
+        .unwrap_or(NICE_STEPS[NICE_STEPS.len() - 1])
+}
+
+/// Format a time label as m:ss, adding tenths when ticks are sub-second and hundredths
+/// when they are closer than a tenth
+fn format_time_label(t: f64, step_secs: f64) -> String {
+    let minutes = (t / 60.0).floor() as i32;
+    if step_secs < 0.1 {
+        format!("{}:{:05.2}", minutes, t % 60.0)
+    } else if step_secs < 1.0 {
+        format!("{}:{:04.1}", minutes, t % 60.0)
+    } else {
+        format!("{}:{:02}", minutes, (t % 60.0).round() as i32)
+    }
+}
+
+/// Draw faint full-height vertical lines every `step_secs` seconds
+fn draw_time_grid(
+    img: &mut RgbImage,
+    start_secs: f64,
+    duration_secs: f64,
+    width: u32,
+    height: u32,
+    step_secs: f64,
+    pen: Pen,
+) {
+    const GRID_ALPHA: f32 = 0.35;
+
+    // Lines closer than one output pixel would only repaint the same columns
+    let step_secs = step_secs.max(duration_secs * pen.scale as f64 / width.max(1) as f64);
+    // First grid line strictly right of the left edge
+    let mut tick = (start_secs / step_secs).floor() as u64 + 1;
+    loop {
+        // Multiply rather than accumulate so tiny steps can't stall or drift
+        let t = tick as f64 * step_secs;
+        if t >= start_secs + duration_secs {
+            break;
         }
-        t += step_secs;
+        let x = (width as f64 * (t - start_secs) / duration_secs) as u32;
+        for x in (x..x + pen.scale).filter(|&x| x < width) {
+            for y in 0..height {
+                let p = img.get_pixel_mut(x, y);
+                for (c, &l) in p.0.iter_mut().zip(pen.color.0.iter()) {
+                    *c = (*c as f32 * (1.0 - GRID_ALPHA) + l as f32 * GRID_ALPHA) as u8;
+                }
+            }
+        }
+        tick += 1;
     }
 }
 
-fn draw_rolloff_line(
+/// Draw a per-column frequency series (Hz) as a polyline; 0.0 entries break the line
+fn draw_frequency_line(
     img: &mut RgbImage,
-    rolloff_freqs: &[f32],
+    freqs: &[f32],
     width: u32,
     height: u32,
-    nyquist: f32,
+    range: (f32, f32),
     linear: bool,
-    color: Rgb<u8>,
+    pen: Pen,
 ) {
-    let min_freq = 20.0f32;
     let height_f = height as f32;
     
     let mut prev_point: Option<(f32, f32)> = None;
     
-    for (i, &freq) in rolloff_freqs.iter().enumerate() {
-        let x = (i as f32 / rolloff_freqs.len() as f32) * width as f32;
-        
-        // Convert frequency to Y position
-        let y = if linear {
-            let y_ratio = freq / nyquist;
-            height_f * (1.0 - y_ratio)
-        } else {
-            if freq < min_freq {
-                height_f - 1.0
-            } else {
-                let y_ratio = (freq / min_freq).log10() / (nyquist / min_freq).log10();
-                height_f - 1.0 - (y_ratio * height_f)
-            }
-        };
+    for (i, &freq) in freqs.iter().enumerate() {
+        let x = (i as f32 / freqs.len() as f32) * width as f32;
+
+        // Silent columns break the line
+        if freq <= 0.0 {
+            prev_point = None;
+            continue;
+        }
         
-        let y = y.max(0.0).min(height_f - 1.0);
+        let y = freq_to_y(freq, range, height, linear).max(0.0).min(height_f - 1.0);
         
         if let Some((px, py)) = prev_point {
-            draw_line_segment_mut(img, (px, py), (x, y), color);
+            pen.line(img, (px, py), (x, y));
         }
         
         prev_point = Some((x, y));
     }
 }
 
+/// Draw dashed horizontal lines at each harmonic's y position, labelled "f", "2f", "3f", ...
+fn draw_harmonic_markers<F>(
+    img: &mut RgbImage,
+    positions: &[f32],
+    width: u32,
+    height: u32,
+    pen: Pen,
+    draw_text: &F,
+) where F: Fn(&mut RgbImage, &str, i32, i32) {
+    let dash = 6 * pen.scale;
+
+    for (i, &y) in positions.iter().enumerate() {
+        if y < 0.0 || y >= height as f32 {
+            continue;
+        }
+
+        for x in (0..width).step_by(2 * dash as usize) {
+            let x_end = (x + dash).min(width) as f32;
+            pen.line(img, (x as f32, y), (x_end, y));
+        }
+
+        let label = if i == 0 { "f".to_string() } else { format!("{}f", i + 1) };
+        draw_text(img, &label, width as i32 - pen.pxi(30), y as i32 - pen.pxi(16));
+    }
+}
+
+/// Map a frequency to a (fractional) y position in a spectrogram of `height` pixels
+/// covering `range` (Hz): the inverse of `spectrogram::row_frequency`, so row `y` shows
+/// exactly the frequency drawn there. Frequencies below the log-scale floor map to the bottom row.
+fn freq_to_y(freq: f32, range: (f32, f32), height: u32, linear: bool) -> f32 {
+    let (min_freq, max_freq) = range;
+    let height_f = height as f32;
+
+    if linear {
+        let y_ratio = (freq - min_freq) / (max_freq - min_freq);
+        height_f - 1.0 - (y_ratio * height_f)
+    } else if freq < min_freq {
+        height_f - 1.0
+    } else {
+        let y_ratio = (freq / min_freq).log10() / (max_freq / min_freq).log10();
+        height_f - 1.0 - (y_ratio * height_f)
+    }
+}
+
+/// Faintly lighten every other octave (C to C) like a piano roll
+fn draw_octave_bands(img: &mut RgbImage, width: u32, height: u32, range: (f32, f32)) {
+    const C0_HZ: f32 = 16.3516;
+    const SHADE_ALPHA: f32 = 0.08;
+
+    let mut octave = 0;
+    let mut low = C0_HZ;
+    while low < range.1 {
+        let high = (low * 2.0).min(range.1);
+
+        // Octaves entirely below a zoomed-in slice aren't visible
+        if octave % 2 == 1 && high > range.0 {
+            let y_top = freq_to_y(high, range, height, false).max(0.0) as u32;
+            let y_bottom = (freq_to_y(low, range, height, false).max(0.0) as u32).min(height - 1);
+
+            for y in y_top..=y_bottom {
+                for x in 0..width {
+                    let p = img.get_pixel_mut(x, y);
+                    for c in p.0.iter_mut() {
+                        *c = (*c as f32 + (255.0 - *c as f32) * SHADE_ALPHA) as u8;
+                    }
+                }
+            }
+        }
+
+        low *= 2.0;
+        octave += 1;
+    }
+}
+
 fn draw_color_bar<F>(
     img: &mut RgbImage,
     stops: &[ColorStop],
     spec_width: u32,
     height: u32,
+    options: &RenderOptions,
     draw_text: &F,
 ) where F: Fn(&mut RgbImage, &str, i32, i32) {
-    let bar_x = spec_width + LEGEND_PADDING;
-    let bar_width = 15;
-    let bar_margin = 20;
+    let ss = options.supersample.max(1);
+    let bar_x = spec_width + LEGEND_PADDING * ss;
+    let bar_width = 15 * ss;
+    let bar_margin = 20 * ss;
     let bar_height = height - 2 * bar_margin;
     
     // Create gradient for the bar
@@ -288,17 +938,58 @@ fn draw_color_bar<F>(
     }
     
     // Draw border around bar
-    let border_color = Rgb([150, 150, 150]);
-    draw_line_segment_mut(img, (bar_x as f32, bar_margin as f32), ((bar_x + bar_width) as f32, bar_margin as f32), border_color);
-    draw_line_segment_mut(img, (bar_x as f32, (bar_margin + bar_height) as f32), ((bar_x + bar_width) as f32, (bar_margin + bar_height) as f32), border_color);
-    draw_line_segment_mut(img, (bar_x as f32, bar_margin as f32), (bar_x as f32, (bar_margin + bar_height) as f32), border_color);
-    draw_line_segment_mut(img, ((bar_x + bar_width) as f32, bar_margin as f32), ((bar_x + bar_width) as f32, (bar_margin + bar_height) as f32), border_color);
+    let border = Pen { color: Rgb([150, 150, 150]), scale: ss };
+    border.line(img, (bar_x as f32, bar_margin as f32), ((bar_x + bar_width) as f32, bar_margin as f32));
+    border.line(img, (bar_x as f32, (bar_margin + bar_height) as f32), ((bar_x + bar_width) as f32, (bar_margin + bar_height) as f32));
+    border.line(img, (bar_x as f32, bar_margin as f32), (bar_x as f32, (bar_margin + bar_height) as f32));
+    border.line(img, ((bar_x + bar_width) as f32, bar_margin as f32), ((bar_x + bar_width) as f32, (bar_margin + bar_height) as f32));
     
-    // Draw dB labels
-    let label_x = (bar_x + bar_width + 3) as i32;
-    draw_text(img, "0dB", label_x, bar_margin as i32);
-    draw_text(img, "-50", label_x, (bar_margin + bar_height / 2) as i32 - 5);
-    draw_text(img, "-100", label_x, (bar_margin + bar_height) as i32 - 12);
+    // Noise floor: a line across the bar that sticks out to the left, towards the spectrogram
+    if let Some(level) = options.noise_floor_level {
+        let floor = Pen { color: Rgb([255, 140, 0]), scale: ss }; // Orange noise floor marker
+        let y = (bar_margin + bar_height) as f32 - level * bar_height as f32;
+        floor.line(img, ((bar_x - 5 * ss) as f32, y), ((bar_x + bar_width) as f32, y));
+        floor.line(img, ((bar_x - 5 * ss) as f32, y + floor.px(1.0)), ((bar_x + bar_width) as f32, y + floor.px(1.0)));
+    }
+
+    // Draw scale labels top to bottom, shifting each up by its share of the text height
+    // so the first hangs below the top edge and the last sits above the bottom edge.
+    // The labels are evenly spaced in value, so a color curve moves them along the bar
+    let labels = legend_labels(options.amplitude, options.phase, options.legend_ticks, options.scale_top_db);
+    let label_x = (bar_x + bar_width + 3 * ss) as i32;
+    let last = labels.len().saturating_sub(1).max(1) as f32;
+    for (i, label) in labels.iter().enumerate() {
+        let t = if options.phase {
+            i as f32 / last
+        } else {
+            1.0 - apply_color_curve(options.color_curve, 1.0 - i as f32 / last)
+        };
+        let y = bar_margin as f32 + t * bar_height as f32 - t * 12.0 * ss as f32;
+        draw_text(img, label, label_x, y.round() as i32);
+    }
+
+    // Palette caption in the margin under the bar
+    draw_text(img, &options.palette_name, (spec_width + 2 * ss) as i32, (bar_margin + bar_height + 3 * ss) as i32);
+}
+
+/// Evenly spaced color bar labels from the top of the scale to the bottom:
+/// 0 to -DB_RANGE dB relative to the peak (or absolute `top_db` to `top_db - DB_RANGE` when the
+/// top is fixed), 1.0 to 0.0 in linear amplitude mode, or 180° to -180° for phase
+fn legend_labels(amplitude: AmplitudeScale, phase: bool, ticks: u32, top_db: Option<f32>) -> Vec<String> {
+    let ticks = ticks.max(2);
+    (0..ticks)
+        .map(|i| {
+            let t = i as f32 / (ticks - 1) as f32;
+            match (amplitude, top_db) {
+                _ if phase => format!("{:.0}°", 180.0 - t * 360.0),
+                (AmplitudeScale::Db, Some(top)) if i == 0 => format!("{:.0}dB", top),
+                (AmplitudeScale::Db, Some(top)) => format!("{:.0}", top - t * DB_RANGE),
+                (AmplitudeScale::Db, None) if i == 0 => "0dB".to_string(),
+                (AmplitudeScale::Db, None) => format!("{:.0}", -t * DB_RANGE),
+                (AmplitudeScale::Linear, _) => format!("{:.1}", 1.0 - t),
+            }
+        })
+        .collect()
 }
 
 fn create_gradient_map(stops: &[ColorStop], size: usize) -> Vec<Rgb<u8>> {
@@ -0,0 +1,410 @@
+use anyhow::{bail, Result, Context};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::analysis::{HarmonicAnalysis, MaxFreqEnergy, PeakLevels, SpectrumAnalysis, StereoImage, TonalPeak, Verdict};
+use crate::decoder::AudioData;
+use crate::reference::ReferenceDeviation;
+use crate::spectrogram::FeatureSeries;
+
+/// Machine-readable summary of a single analysis run
+#[derive(Debug, Serialize)]
+pub struct AnalysisSummary {
+    pub file: PathBuf,
+    pub container: Option<String>,
+    pub codec: String,
+    pub sample_rate: u32,
+    pub channel_layout: String,
+    pub bits_per_sample: Option<u32>,
+    pub bit_rate: Option<u64>,
+    /// ReplayGain tag values, if present
+    pub track_gain_db: Option<f32>,
+    pub album_gain_db: Option<f32>,
+    /// Gain applied before analysis by `--apply-gain`
+    pub applied_gain_db: Option<f32>,
+    pub duration_secs: f64,
+    /// Highest sample and true peak over the source channels
+    #[serde(flatten)]
+    pub peaks: PeakLevels,
+    /// Whether the true peak is above 0 dBTP
+    pub true_peak_over: bool,
+    /// Packets that failed to decode and were skipped, and the sample frames they held
+    pub corrupt_packets: usize,
+    pub skipped_samples: u64,
+    /// Channel correlation, for stereo files
+    pub stereo: Option<StereoImage>,
+    /// Integrated loudness of the source (before `--loudness-normalize`)
+    pub loudness_lufs: Option<f32>,
+    /// Gain applied to the analyzed signal by `--loudness-normalize`, in dB
+    pub loudness_gain_db: Option<f32>,
+    pub params: AnalysisParams,
+    pub rolloff: Option<RolloffStats>,
+    pub spectral_flux: Option<FluxStats>,
+    pub spectral_entropy: Option<EntropyStats>,
+    pub cutoff_hz: Option<f32>,
+    /// Highest frequency above a relative level, if `--max-freq-energy` was given
+    pub max_freq_energy: Option<MaxFreqEnergy>,
+    /// Noise floor per FFT bin (dBFS), if `--noise-floor` was given
+    pub noise_floor_db: Option<f32>,
+    pub verdict: Verdict,
+    /// Deviation from a `--reference` curve, if one was given
+    pub reference: Option<Vec<ReferenceDeviation>>,
+    /// Fundamental and harmonic levels, if `--harmonics` was given
+    pub harmonics: Option<HarmonicAnalysis>,
+    /// Loudest tonal peaks, if `--peaks` was given
+    pub tonal_peaks: Option<Vec<TonalPeak>>,
+    /// Hash of the spectral content as 16 hex digits, if `--fingerprint` was given
+    pub fingerprint: Option<String>,
+}
+
+/// Result of `--check`: file properties and the quality verdict, without rendering
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub file: PathBuf,
+    pub codec: String,
+    pub sample_rate: u32,
+    pub duration_secs: f64,
+    /// Packets that failed to decode and were skipped, and the sample frames they held
+    pub corrupt_packets: usize,
+    pub skipped_samples: u64,
+    pub cutoff_hz: Option<f32>,
+    pub verdict: Verdict,
+    /// Hash of the spectral content as 16 hex digits, if `--fingerprint` was given
+    pub fingerprint: Option<String>,
+}
+
+impl CheckReport {
+    pub fn new(file: &Path, audio_data: &AudioData, spectrum: &SpectrumAnalysis) -> Self {
+        CheckReport {
+            file: file.to_path_buf(),
+            codec: audio_data.metadata.codec.clone(),
+            sample_rate: audio_data.sample_rate,
+            duration_secs: audio_data.duration_secs,
+            corrupt_packets: audio_data.corrupt_packets,
+            skipped_samples: audio_data.skipped_samples,
+            cutoff_hz: spectrum.cutoff_hz,
+            verdict: spectrum.verdict,
+            fingerprint: None,
+        }
+    }
+
+    /// Serialize the report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize check report")
+    }
+}
+
+/// A fingerprint from `analysis::fingerprint` as fixed-width hex
+pub fn fingerprint_hex(fingerprint: u64) -> String {
+    format!("{:016x}", fingerprint)
+}
+
+/// A file that could not be analyzed, reported in place of its summary in a JSON-lines batch
+#[derive(Debug, Serialize)]
+pub struct FileError {
+    pub file: PathBuf,
+    pub error: String,
+}
+
+/// Serialize a report as compact single-line JSON, for JSON-lines output
+pub fn to_json_line<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).context("Failed to serialize JSON line")
+}
+
+/// Settings the spectrogram was produced with
+#[derive(Debug, Serialize)]
+pub struct AnalysisParams {
+    pub width: u32,
+    pub height: u32,
+    pub freq_scale: &'static str,
+    pub palette: String,
+    pub fft_size: usize,
+    /// FFT length as a multiple of `fft_size` (the window), from `--zero-pad`
+    pub zero_pad: usize,
+    pub hop_size: usize,
+    /// Fraction of each frame shared with the next one
+    pub overlap: f32,
+    pub window: &'static str,
+    /// Color mapping of magnitudes ("db" or "linear")
+    pub amplitude: String,
+    /// Curve from color scale position to palette ("linear", "sqrt" or "log")
+    pub color_curve: String,
+    /// Dynamic range below the peak mapped onto the palette (dB amplitude only)
+    pub db_range: f32,
+    /// What the color scale is normalized to
+    pub normalization: String,
+    /// How frames were mapped onto image columns ("interpolate" or "max")
+    pub time_mapping: String,
+    /// Lowest FFT bins zeroed before analysis (1 = DC only)
+    pub dc_bins_removed: usize,
+    pub silence_db: f32,
+    pub a_weighting: bool,
+    /// Whether the audio was reversed in time (`--reverse`)
+    pub reversed: bool,
+    /// Display tilt in dB per octave around 1 kHz, if `--tilt` was given
+    pub tilt_db_per_octave: Option<f32>,
+    /// Level at the top of the color scale when fixed by `--ref-db` or shared across a batch (dBFS)
+    pub reference_db: Option<f32>,
+    /// Energy weighting used for the rolloff ("power" or "magnitude")
+    pub rolloff_metric: String,
+    /// Frequency span covered by the image height (Hz)
+    pub freq_min_hz: f32,
+    pub freq_max_hz: f32,
+}
+
+/// Aggregate statistics over the non-silent per-column rolloff frequencies (Hz)
+#[derive(Debug, Serialize)]
+pub struct RolloffStats {
+    pub min_hz: f32,
+    pub median_hz: f32,
+    pub mean_hz: f32,
+    pub max_hz: f32,
+}
+
+/// Flux below this is never counted as an onset, so steady signals don't report noise as onsets
+const MIN_ONSET_FLUX: f32 = 0.01;
+
+/// How "busy" the track is: average spectral flux and the rate of onsets (flux peaks)
+#[derive(Debug, Serialize)]
+pub struct FluxStats {
+    /// Mean flux per frame transition (full-scale units)
+    pub mean: f32,
+    /// Local flux maxima more than two standard deviations above the mean
+    /// (and above `MIN_ONSET_FLUX`), per second
+    pub onsets_per_sec: f32,
+}
+
+/// Normalized spectral entropy over the non-silent frames (0 = pure tone, 1 = white noise)
+#[derive(Debug, Serialize)]
+pub struct EntropyStats {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl AnalysisSummary {
+    pub fn new(
+        file: &Path,
+        audio_data: &AudioData,
+        peaks: PeakLevels,
+        params: AnalysisParams,
+        rolloff_frequencies: Option<&[f32]>,
+        spectrum: &SpectrumAnalysis,
+    ) -> Self {
+        AnalysisSummary {
+            file: file.to_path_buf(),
+            container: audio_data.metadata.container.clone(),
+            codec: audio_data.metadata.codec.clone(),
+            sample_rate: audio_data.sample_rate,
+            channel_layout: audio_data.metadata.channel_layout.clone(),
+            bits_per_sample: audio_data.metadata.bits_per_sample,
+            bit_rate: audio_data.metadata.bit_rate,
+            track_gain_db: audio_data.metadata.replay_gain.track_gain_db,
+            album_gain_db: audio_data.metadata.replay_gain.album_gain_db,
+            applied_gain_db: audio_data.applied_gain_db,
+            duration_secs: audio_data.duration_secs,
+            peaks,
+            true_peak_over: peaks.is_over(),
+            corrupt_packets: audio_data.corrupt_packets,
+            skipped_samples: audio_data.skipped_samples,
+            stereo: audio_data.stereo,
+            loudness_lufs: audio_data.loudness_lufs,
+            loudness_gain_db: None,
+            params,
+            rolloff: rolloff_frequencies.and_then(RolloffStats::from_frequencies),
+            spectral_flux: None,
+            spectral_entropy: None,
+            cutoff_hz: spectrum.cutoff_hz,
+            max_freq_energy: None,
+            noise_floor_db: None,
+            verdict: spectrum.verdict,
+            reference: None,
+            harmonics: None,
+            tonal_peaks: None,
+            fingerprint: None,
+        }
+    }
+
+    /// Serialize the summary as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize analysis summary")
+    }
+}
+
+impl RolloffStats {
+    fn from_frequencies(freqs: &[f32]) -> Option<Self> {
+        let mut sorted: Vec<f32> = freqs.iter().copied().filter(|&f| f > 0.0).collect();
+        if sorted.is_empty() {
+            return None;
+        }
+
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let mean = sorted.iter().sum::<f32>() / sorted.len() as f32;
+
+        Some(RolloffStats {
+            min_hz: sorted[0],
+            median_hz: sorted[sorted.len() / 2],
+            mean_hz: mean,
+            max_hz: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl FluxStats {
+    /// Summarize per-transition flux values produced at `frames_per_sec`
+    pub fn from_flux(flux: &[f32], frames_per_sec: f32) -> Option<Self> {
+        if flux.is_empty() {
+            return None;
+        }
+
+        let n = flux.len() as f32;
+        let mean = flux.iter().sum::<f32>() / n;
+        let std_dev = (flux.iter().map(|f| (f - mean).powi(2)).sum::<f32>() / n).sqrt();
+        let threshold = (mean + 2.0 * std_dev).max(MIN_ONSET_FLUX);
+
+        let onsets = flux.windows(3)
+            .filter(|w| w[1] > threshold && w[1] > w[0] && w[1] >= w[2])
+            .count();
+
+        Some(FluxStats {
+            mean,
+            onsets_per_sec: onsets as f32 * frames_per_sec / n,
+        })
+    }
+}
+
+impl EntropyStats {
+    /// Summarize per-frame entropies from `compute_spectral_entropy`
+    pub fn from_entropy(entropy: &[f32]) -> Option<Self> {
+        if entropy.is_empty() {
+            return None;
+        }
+
+        Some(EntropyStats {
+            // Summed in f64 so the mean of near-identical frames stays between min and max
+            mean: (entropy.iter().map(|&e| e as f64).sum::<f64>() / entropy.len() as f64) as f32,
+            min: entropy.iter().copied().fold(f32::INFINITY, f32::min),
+            max: entropy.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        })
+    }
+}
+
+/// Path of the JSON sidecar written next to a saved image (`out.png` -> `out.json`)
+pub fn sidecar_path(image_path: &Path) -> PathBuf {
+    image_path.with_extension("json")
+}
+
+/// Write the summary as a JSON sidecar next to the saved image
+pub fn write_sidecar(image_path: &Path, summary: &AnalysisSummary) -> Result<PathBuf> {
+    let path = sidecar_path(image_path);
+    fs::write(&path, summary.to_json()?)
+        .with_context(|| format!("Failed to write sidecar to {:?}", path))?;
+    Ok(path)
+}
+
+/// Write the per-column feature series as CSV: `time_secs,rolloff_hz,centroid_hz,peak_hz`, one
+/// row per image column starting at its left edge. Silent columns have empty feature cells.
+pub fn write_series_csv(path: &Path, series: &FeatureSeries, duration_secs: f64) -> Result<()> {
+    let columns = series.rolloff_hz.len();
+    let cell = |hz: f32| if hz > 0.0 { format!("{:.1}", hz) } else { String::new() };
+
+    let mut csv = String::from("time_secs,rolloff_hz,centroid_hz,peak_hz\n");
+    for x in 0..columns {
+        let time = x as f64 * duration_secs / columns as f64;
+        csv.push_str(&format!(
+            "{:.4},{},{},{}\n",
+            time, cell(series.rolloff_hz[x]), cell(series.centroid_hz[x]), cell(series.peak_hz[x]),
+        ));
+    }
+    fs::write(path, csv).with_context(|| format!("Failed to write series CSV to {:?}", path))
+}
+
+/// Rolloff and centroid series read back from a `--series-csv` file, to draw over another render
+#[derive(Debug, Clone)]
+pub struct SeriesOverlay {
+    /// Start time of each row (s)
+    pub time_secs: Vec<f64>,
+    /// Per-row values (Hz); 0.0 where the cell was empty (silent)
+    pub rolloff_hz: Vec<f32>,
+    pub centroid_hz: Vec<f32>,
+}
+
+impl SeriesOverlay {
+    /// Read a CSV in the `write_series_csv` format. Columns are found by their header names, so
+    /// only `time_secs` and at least one of `rolloff_hz` and `centroid_hz` are required.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read series CSV: {:?}", path))?;
+        Self::parse(&content)
+            .with_context(|| format!("Failed to parse series CSV: {:?}", path))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut lines = content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+        let (_, header) = lines.next().context("file is empty")?;
+        let header: Vec<&str> = header.split(',').map(str::trim).collect();
+        let column = |name: &str| header.iter().position(|&h| h == name);
+        let time_column = column("time_secs").context("missing a time_secs column")?;
+        let (rolloff_column, centroid_column) = (column("rolloff_hz"), column("centroid_hz"));
+        if rolloff_column.is_none() && centroid_column.is_none() {
+            bail!("missing both rolloff_hz and centroid_hz columns");
+        }
+
+        let mut series = SeriesOverlay { time_secs: Vec::new(), rolloff_hz: Vec::new(), centroid_hz: Vec::new() };
+        for (i, line) in lines {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let time: f64 = fields.get(time_column)
+                .and_then(|t| t.parse().ok())
+                .with_context(|| format!("line {}: invalid time in {:?}", i + 1, line))?;
+            if series.time_secs.last().is_some_and(|&last| time <= last) {
+                bail!("line {}: time {} does not increase", i + 1, time);
+            }
+            let hz = |column: Option<usize>| -> Result<f32> {
+                match column.and_then(|c| fields.get(c)).copied().unwrap_or_default() {
+                    "" => Ok(0.0),
+                    cell => cell.parse()
+                        .with_context(|| format!("line {}: invalid frequency {:?}", i + 1, cell)),
+                }
+            };
+            series.rolloff_hz.push(hz(rolloff_column)?);
+            series.centroid_hz.push(hz(centroid_column)?);
+            series.time_secs.push(time);
+        }
+
+        if series.time_secs.is_empty() {
+            bail!("no rows");
+        }
+        Ok(series)
+    }
+
+    /// The rolloff and centroid lines (those with any values) resampled onto `columns` image
+    /// columns spanning `duration_secs`, in the per-column form the overlays are drawn from.
+    pub fn lines(&self, duration_secs: f64, columns: usize) -> Vec<Vec<f32>> {
+        [&self.rolloff_hz, &self.centroid_hz].into_iter()
+            .filter(|values| values.iter().any(|&hz| hz > 0.0))
+            .map(|values| self.resample(values, duration_secs, columns))
+            .collect()
+    }
+
+    /// Each column takes the row whose span holds its center time. The last row spans as long
+    /// as the one before it; columns outside the rows are left at 0.0, which breaks the line.
+    fn resample(&self, values: &[f32], duration_secs: f64, columns: usize) -> Vec<f32> {
+        let times = &self.time_secs;
+        let last_step = match times.len() {
+            1 => f64::INFINITY,
+            n => times[n - 1] - times[n - 2],
+        };
+
+        let mut row = 0;
+        (0..columns)
+            .map(|x| {
+                let t = (x as f64 + 0.5) * duration_secs / columns as f64;
+                while row + 1 < times.len() && times[row + 1] <= t {
+                    row += 1;
+                }
+                let covered = t >= times[0] && (row + 1 < times.len() || t < times[row] + last_step);
+                if covered { values[row] } else { 0.0 }
+            })
+            .collect()
+    }
+}
@@ -1,95 +1,468 @@
-use anyhow::Result;
-use rustfft::{FftPlanner, num_complex::Complex};
-use image::{RgbImage, Rgb};
+use anyhow::{bail, Context, Result};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use image::{ImageBuffer, RgbImage, Rgb};
 use crate::config::{ColorStop, Config};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::{AmplitudeScale, ColorCurve, Normalization, RolloffMetric, SpectrogramMode, TimeMapping};
+use crate::progress::{self, ProgressCallback, Stage};
 use rayon::prelude::*;
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default FFT size (samples per analysis frame)
+pub const WINDOW_SIZE: usize = 2048;
+
+/// Samples between consecutive frames at the default FFT size (75% overlap)
+pub const HOP_SIZE: usize = hop_size(WINDOW_SIZE);
+
+/// Samples between consecutive frames for an FFT of `window_size` (75% overlap)
+pub const fn hop_size(window_size: usize) -> usize {
+    if window_size >= 4 { window_size / 4 } else { 1 }
+}
+
+/// Window function applied to each frame
+pub const WINDOW_FUNCTION: &str = "hann";
+
+/// Dynamic range shown below the peak in dB amplitude mode
+pub const DB_RANGE: f32 = 100.0;
+
+/// Percentile of per-frame peaks used as the reference level by `Normalization::Robust`
+const ROBUST_PERCENTILE: f32 = 0.99;
+
+/// Percentile of per-frame median bin levels taken as the noise floor: the quieter passages,
+/// where the median bin is noise rather than signal
+const NOISE_FLOOR_PERCENTILE: f32 = 0.1;
+
+/// Lowest frequency shown on the log scale (Hz)
+pub const LOG_MIN_FREQ: f32 = 20.0;
+
+/// Frequency (Hz) left unchanged by the display tilt
+pub const TILT_PIVOT_HZ: f32 = 1000.0;
+
+/// FFT size of the extra low-frequency analysis used by the log scale, relative to the
+/// regular FFT size (4x the bin resolution)
+pub const BASS_WINDOW_FACTOR: usize = 4;
+
+/// Below this frequency (Hz) the log scale is drawn from the low-frequency analysis alone;
+/// up to twice this it crossfades into the regular one
+const BASS_CROSSOVER: f32 = 250.0;
 
 /// Result containing spectrogram image, optional rolloff data, and STFT for quality analysis
 pub struct SpectrogramResult {
     pub image: RgbImage,
+    /// Color scale position (0.0..=1.0) of every image pixel, row by row; kept when `keep_levels` is set
+    pub levels: Option<Vec<f32>>,
     pub rolloff_frequencies: Option<Vec<f32>>, // Hz per time frame
+    pub centroid_frequencies: Option<Vec<f32>>, // Hz per time frame
+    pub cutoff_frequencies: Option<Vec<f32>>, // Hz per time frame
+    /// Noise floor per FFT bin (dBFS), if `compute_noise_floor` was set (see `noise_floor_db`)
+    pub noise_floor_db: Option<f32>,
+    /// Color scale position (0.0..=1.0) of the noise floor, for marking it on the color bar
+    pub noise_floor_level: Option<f32>,
+    /// Unsmoothed per-column feature series, if `series_width` was set
+    pub series: Option<FeatureSeries>,
+    /// Per-frame features from the single stats pass (see `compute_frame_stats`)
+    pub frame_stats: Vec<FrameStats>,
     pub stft: StftResult, // For quality analysis
 }
 
+/// Per-frame features resampled to one value per image column, without smoothing.
+/// Silent frames are 0.0 in every series.
+pub struct FeatureSeries {
+    pub rolloff_hz: Vec<f32>,
+    pub centroid_hz: Vec<f32>,
+    /// Frequency of the loudest bin
+    pub peak_hz: Vec<f32>,
+}
+
+/// Options controlling spectrogram analysis and rendering
+pub struct SpectrogramOptions {
+    /// FFT size; any even size works (see `compute_stft`)
+    pub window_size: usize,
+    /// Zero-pad each windowed frame to this many times `window_size` (1 = no padding)
+    pub zero_pad: usize,
+    pub width: u32,
+    pub height: u32,
+    pub linear: bool,
+    pub compute_rolloff: bool,
+    /// Weighting of bins when accumulating rolloff energy
+    pub rolloff_metric: RolloffMetric,
+    /// Moving-average length for the rolloff trace, in STFT frames (1 = no smoothing)
+    pub rolloff_smooth: usize,
+    pub compute_centroid: bool,
+    /// Moving-average length for the centroid trace, in STFT frames (1 = no smoothing)
+    pub centroid_smooth: usize,
+    /// Gather per-frame spectral entropy into `frame_stats`
+    pub compute_entropy: bool,
+    /// Estimate the noise floor (see `noise_floor_db`)
+    pub compute_noise_floor: bool,
+    /// Collect the rolloff, centroid and peak frequency as `series`, whether or not they're drawn,
+    /// with this many columns (the output width, which supersampling doesn't change)
+    pub series_width: Option<u32>,
+    pub amplitude: AmplitudeScale,
+    /// Curve from color scale position to palette position
+    pub color_curve: ColorCurve,
+    /// Reference level the color scale is anchored to
+    pub normalize: Normalization,
+    /// How frames are mapped onto columns when downsampling in time
+    pub time_mapping: TimeMapping,
+    /// Frames whose loudest bin is below this level (dBFS) are treated as silent
+    pub silence_db: f32,
+    /// Trace the highest frequency above this level (dBFS), if set
+    pub cutoff_db: Option<f32>,
+    /// Apply the A-weighting curve to magnitudes before color mapping
+    pub a_weight: bool,
+    /// Boost (or cut) magnitudes by this many dB per octave above 1 kHz before color mapping
+    pub tilt_db_per_octave: Option<f32>,
+    /// Lower edge of the displayed frequency slice (Hz)
+    pub freq_min: Option<f32>,
+    /// Upper edge of the displayed frequency slice (Hz)
+    pub freq_max: Option<f32>,
+    /// Keep only the FFT bins up to this frequency (Hz) to save memory and time; `freq_max`
+    /// should not go above it, or the top rows repeat the last kept bin
+    pub analysis_max_freq: Option<f32>,
+    /// On the log scale, draw the lowest octaves from a longer FFT for finer bass detail
+    pub bass_detail: bool,
+    /// Narrow spectral ridges for display with this strength (see `sharpen_ridges`)
+    pub sharpen: Option<f32>,
+    /// Skip the FFT of frames whose windowed RMS level is below this (dBFS), leaving them silent
+    pub fft_gate_db: Option<f32>,
+    /// Number of lowest bins (DC and its neighbour) zeroed before analysis and display
+    pub dc_bins: usize,
+    /// Render the spectrogram image; when false `image` is left empty (e.g. for barcode mode)
+    pub render_image: bool,
+    /// Show magnitude, or phase on a cyclic palette (the `config` stops must then be cyclic)
+    pub mode: SpectrogramMode,
+    /// Keep the per-pixel color scale positions so the image can be recolored at a higher bit depth
+    pub keep_levels: bool,
+    /// Paint pixels at the very bottom of the color scale (at or below the floor) in this color
+    pub floor_color: Option<Rgb<u8>>,
+    /// Break up banding in smooth gradients with ordered dithering (see `colorize_levels_dithered`)
+    pub dither: bool,
+    /// Anchor the top of the color scale at this level (dBFS) instead of the `normalize` reference
+    /// of this file, e.g. to render several files against one shared level
+    pub reference_db: Option<f32>,
+}
+
+/// Frequency span (Hz) covered by the image height: the full band unless a slice was requested.
+/// The log scale never starts below `LOG_MIN_FREQ`, and nothing goes above Nyquist.
+pub fn display_range(sample_rate: u32, linear: bool, freq_min: Option<f32>, freq_max: Option<f32>) -> (f32, f32) {
+    let nyquist = sample_rate as f32 / 2.0;
+    let floor = if linear { 0.0 } else { LOG_MIN_FREQ };
+    (
+        freq_min.unwrap_or(floor).max(floor),
+        freq_max.unwrap_or(nyquist).min(nyquist),
+    )
+}
+
 pub fn generate_spectrogram(
     samples: &[f32],
     sample_rate: u32,
-    width: u32,
-    height: u32,
     config: &Config,
-    linear: bool,
-    quiet: bool,
-    compute_rolloff: bool,
+    options: &SpectrogramOptions,
+    progress: Option<&ProgressCallback>,
 ) -> Result<SpectrogramResult> {
-    let window_size = 2048;
-    let overlap = 0.75; // 75% overlap
-    let hop_size = (window_size as f32 * (1.0 - overlap)) as usize;
-    
-    if samples.len() < window_size {
-         return Err(anyhow::anyhow!("File too short (need at least {} samples)", window_size));
-    }
+    let window_size = options.window_size;
+    let samples = &*pad_to_window(samples, window_size);
+    frame_count(samples.len(), window_size, hop_size(window_size))
+        .context("Audio too short for the FFT size")?;
 
     // Step 1: Compute STFT
-    let stft_result = compute_stft(samples, window_size, hop_size, quiet)?;
+    let fft_size = window_size * options.zero_pad;
+    let num_bins = bins_up_to(options.analysis_max_freq, sample_rate, fft_size);
+    let mut stft_result = stft_lowest_bins(samples, window_size, fft_size, hop_size(window_size), num_bins, options.fft_gate_db, progress)?;
+    // Padding interpolates the spectrum, so the same span of it takes `zero_pad` times the bins
+    remove_dc(&mut stft_result, options.dc_bins * options.zero_pad);
     
-    // Step 2: Compute spectral rolloff if requested
-    let rolloff_frequencies = if compute_rolloff {
-        Some(compute_spectral_rolloff(&stft_result, sample_rate, width))
-    } else {
-        None
-    };
+    // Step 2: Gather the per-frame features in one pass. The color scale reference comes from
+    // the same pass unless the display magnitudes differ (sharpening) or it's fixed
+    let bin_gain = bin_gains(&stft_result, sample_rate, options);
+    let fuse_reference = options.render_image && options.sharpen.is_none() && options.reference_db.is_none();
+    let frame_stats = compute_frame_stats(&stft_result, sample_rate, &FrameStatsRequest {
+        silence_db: options.silence_db,
+        rolloff: (options.compute_rolloff || options.series_width.is_some()).then_some(options.rolloff_metric),
+        centroid: options.compute_centroid || options.series_width.is_some(),
+        entropy: options.compute_entropy,
+        median: options.compute_noise_floor,
+        cutoff_db: options.cutoff_db,
+        bin_gain: fuse_reference.then_some(&bin_gain[..]),
+    });
+    let rolloff_frequencies = options.compute_rolloff
+        .then(|| frame_trace(&frame_stats, |s| s.rolloff_hz, options.rolloff_smooth, options.width));
+    let centroid_frequencies = options.compute_centroid
+        .then(|| frame_trace(&frame_stats, |s| s.centroid_hz, options.centroid_smooth, options.width));
+    let cutoff_frequencies = options.cutoff_db
+        .map(|_| frame_trace(&frame_stats, |s| s.cutoff_hz, 1, options.width));
+    let series = options.series_width.map(|width| FeatureSeries {
+        rolloff_hz: frame_trace(&frame_stats, |s| s.rolloff_hz, 1, width),
+        centroid_hz: frame_trace(&frame_stats, |s| s.centroid_hz, 1, width),
+        peak_hz: frame_trace(&frame_stats, |s| s.peak_hz, 1, width),
+    });
+    let noise_floor_db = options.compute_noise_floor
+        .then(|| noise_floor_db(&frame_stats, stft_result.fft_bins))
+        .flatten();
     
     // Step 3: Render to image
-    let img = render_spectrogram(&stft_result, sample_rate, width, height, config, linear, quiet)?;
+    let (img, levels, scale_top_db) = if options.render_image {
+        let mut bass = if options.bass_detail && !options.linear {
+            BassBand::compute(samples, sample_rate, window_size, &stft_result, options.dc_bins, options.fft_gate_db)?
+        } else {
+            None
+        };
+        // Sharpen a copy for display only; analysis keeps the plain magnitudes
+        let sharpened = options.sharpen.map(|strength| {
+            if let Some(ref mut band) = bass {
+                band.frames = sharpen_ridges(&band.frames, strength);
+            }
+            StftResult {
+                magnitudes: sharpen_ridges(&stft_result.magnitudes, strength),
+                num_time_frames: stft_result.num_time_frames,
+                num_freq_bins: stft_result.num_freq_bins,
+                fft_bins: stft_result.fft_bins,
+                gated_frames: stft_result.gated_frames,
+            }
+        });
+        let (levels, scale_top_db) = render_spectrogram(
+            sharpened.as_ref().unwrap_or(&stft_result),
+            bass.as_ref(),
+            sample_rate,
+            options,
+            fuse_reference.then_some(&frame_stats[..]),
+            progress,
+        )?;
+        let gradient = create_gradient_map(&config.colors.stops, 1024);
+        match options.mode {
+            SpectrogramMode::Magnitude if options.dither => {
+                let img = colorize_levels_dithered(
+                    &levels, options.width, options.height, &config.colors.stops, options.floor_color,
+                );
+                (img, options.keep_levels.then_some(levels), Some(scale_top_db))
+            }
+            SpectrogramMode::Magnitude => {
+                let img = RgbImage::from_fn(options.width, options.height, |x, y| {
+                    let level = levels[(y * options.width + x) as usize];
+                    match options.floor_color {
+                        Some(color) if level == 0.0 => color,
+                        _ => gradient[(level * 1023.0) as usize],
+                    }
+                });
+                (img, options.keep_levels.then_some(levels), Some(scale_top_db))
+            }
+            SpectrogramMode::Phase => {
+                let phases = compute_phase(samples, window_size, fft_size, hop_size(window_size), options.fft_gate_db)?;
+                (render_phase(&phases, &levels, &gradient, sample_rate, options), None, None)
+            }
+        }
+    } else {
+        (RgbImage::new(0, 0), None, None)
+    };
+    let noise_floor_level = noise_floor_db.zip(scale_top_db).map(|(floor, top)| {
+        let level = match options.amplitude {
+            AmplitudeScale::Db => (floor - top + DB_RANGE) / DB_RANGE,
+            AmplitudeScale::Linear => 10f32.powf((floor - top) / 20.0),
+        };
+        apply_color_curve(options.color_curve, level.clamp(0.0, 1.0))
+    });
     
     Ok(SpectrogramResult {
         image: img,
+        levels,
         rolloff_frequencies,
+        centroid_frequencies,
+        cutoff_frequencies,
+        noise_floor_db,
+        noise_floor_level,
+        series,
+        frame_stats,
         stft: stft_result,
     })
 }
 
+/// Forward FFT plans keyed by size
+type FftPlans = HashMap<usize, Arc<dyn Fft<f32>>>;
+
+/// FFT plans shared across every file processed in this run
+static FFT_CACHE: OnceLock<Mutex<FftPlans>> = OnceLock::new();
+
+/// Get the forward FFT plan for `size`, planning it only on first use
+fn cached_fft(size: usize) -> Arc<dyn Fft<f32>> {
+    let cache = FFT_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut plans = cache.lock().unwrap();
+    plans.entry(size)
+        .or_insert_with(|| FftPlanner::new().plan_fft_forward(size))
+        .clone()
+}
+
 pub struct StftResult {
     // 2D array: time_frames x frequency_bins
     pub magnitudes: Vec<Vec<f32>>,
     pub num_time_frames: usize,
+    /// Bins kept in each frame: half the FFT size unless the top of the spectrum was dropped.
+    /// Bin `k` is centered on `k * sample_rate / fft_size` Hz, so the bins run from DC up to at
+    /// most one bin below Nyquist; the Nyquist bin itself (real-valued, and empty for
+    /// band-limited audio) is not kept, and rows above the last bin repeat it.
     pub num_freq_bins: usize,
+    /// Half the FFT size, which sets the bin spacing (`sample_rate / (2 * fft_bins)`) and the
+    /// full-scale level (`fft_bins / 2`) however many bins were kept
+    pub fft_bins: usize,
+    /// Frames left at zero magnitude without an FFT because they were below the energy gate
+    pub gated_frames: usize,
 }
 
-fn compute_stft(samples: &[f32], window_size: usize, hop_size: usize, quiet: bool) -> Result<StftResult> {
-    let num_time_frames = (samples.len() - window_size) / hop_size + 1;
-    let num_freq_bins = window_size / 2;
+/// Short-time Fourier transform with a Hann window; magnitudes per frame up to Nyquist.
+/// `samples` must be at least `window_size` long. Any size works (rustfft uses mixed-radix
+/// plans), but it should be even: bins are taken to be `sample_rate / window_size` apart,
+/// `window_size / 2` of them up to Nyquist.
+pub fn compute_stft(
+    samples: &[f32],
+    window_size: usize,
+    hop_size: usize,
+    progress: Option<&ProgressCallback>,
+) -> Result<StftResult> {
+    compute_stft_gated(samples, window_size, hop_size, None, progress)
+}
+
+/// Like `compute_stft_gated`, but each windowed frame is zero-padded to `fft_size` samples
+/// before the FFT: `fft_size / 2` bins up to Nyquist, `sample_rate / fft_size` apart, that
+/// interpolate the window's spectrum without lengthening it in time. Magnitudes are scaled by
+/// `fft_size / window_size` so a full-scale sine still reads 0 dBFS against `num_freq_bins / 2`.
+pub fn compute_stft_padded(
+    samples: &[f32],
+    window_size: usize,
+    fft_size: usize,
+    hop_size: usize,
+    gate_db: Option<f32>,
+    progress: Option<&ProgressCallback>,
+) -> Result<StftResult> {
+    stft_lowest_bins(samples, window_size, fft_size, hop_size, fft_size / 2, gate_db, progress)
+}
+
+/// Like `compute_stft`, but frames whose windowed RMS level is below `gate_db` (dBFS)
+/// skip the FFT and get all-zero magnitudes, which speeds up mostly-silent recordings
+pub fn compute_stft_gated(
+    samples: &[f32],
+    window_size: usize,
+    hop_size: usize,
+    gate_db: Option<f32>,
+    progress: Option<&ProgressCallback>,
+) -> Result<StftResult> {
+    compute_stft_padded(samples, window_size, window_size, hop_size, gate_db, progress)
+}
+
+/// Number of bins of a `fft_size` FFT needed to cover everything up to `max_freq` (Hz), plus one
+/// above it to interpolate towards; all of them up to Nyquist if `max_freq` is None
+pub fn bins_up_to(max_freq: Option<f32>, sample_rate: u32, fft_size: usize) -> usize {
+    let all = fft_size / 2;
+    max_freq.map_or(all, |freq| {
+        let bin_hz = sample_rate as f32 / fft_size as f32;
+        ((freq / bin_hz).ceil() as usize + 1).clamp(1, all)
+    })
+}
+
+/// Like `compute_stft_padded`, but keeping only the lowest `num_freq_bins` bins of each frame
+/// (see `bins_up_to`). Levels and bin frequencies are unchanged: they follow `fft_bins`.
+pub fn stft_lowest_bins(
+    samples: &[f32],
+    window_size: usize,
+    fft_size: usize,
+    hop_size: usize,
+    num_freq_bins: usize,
+    gate_db: Option<f32>,
+    progress: Option<&ProgressCallback>,
+) -> Result<StftResult> {
+    let pad_gain = fft_size as f32 / window_size as f32;
+    let (magnitudes, gated_frames) =
+        stft_map(samples, window_size, fft_size, hop_size, num_freq_bins, gate_db, progress, |c| c.norm() * pad_gain)?;
+
+    Ok(StftResult {
+        num_time_frames: magnitudes.len(),
+        magnitudes,
+        num_freq_bins,
+        fft_bins: fft_size / 2,
+        gated_frames,
+    })
+}
+
+/// Phase (radians, -π..=π) of every bin up to Nyquist, per STFT frame.
+/// Frames below `gate_db` are left at zero like their magnitudes.
+pub fn compute_phase(
+    samples: &[f32],
+    window_size: usize,
+    fft_size: usize,
+    hop_size: usize,
+    gate_db: Option<f32>,
+) -> Result<Vec<Vec<f32>>> {
+    stft_map(samples, window_size, fft_size, hop_size, fft_size / 2, gate_db, None, |c| c.arg())
+        .map(|(phases, _)| phases)
+}
+
+/// `samples` with trailing silence up to `window_size` if it's shorter, so a clip shorter than
+/// one FFT window still gives a single frame instead of none. Empty input stays empty.
+pub fn pad_to_window(samples: &[f32], window_size: usize) -> Cow<'_, [f32]> {
+    if samples.is_empty() || samples.len() >= window_size {
+        return Cow::Borrowed(samples);
+    }
+    let mut padded = samples.to_vec();
+    padded.resize(window_size, 0.0);
+    Cow::Owned(padded)
+}
+
+/// Number of STFT frames that fit in `num_samples`, rejecting parameters that would give
+/// none or divide by zero (a hop of 0, a window too small for a Hann taper, or too few samples)
+pub fn frame_count(num_samples: usize, window_size: usize, hop_size: usize) -> Result<usize> {
+    if window_size < 2 {
+        bail!("FFT window must be at least 2 samples, got {}", window_size);
+    }
+    if hop_size == 0 {
+        bail!("hop size must be at least 1 sample (window {}, hop 0)", window_size);
+    }
+    if num_samples < window_size {
+        bail!("too few samples for one FFT frame: need at least {}, got {}", window_size, num_samples);
+    }
+    Ok((num_samples - window_size) / hop_size + 1)
+}
+
+/// Run a windowed FFT over every frame, zero-padded to `fft_size`, and map the lowest
+/// `num_freq_bins` bins through `value`.
+/// Returns the per-frame values and the number of frames skipped by the energy gate.
+#[allow(clippy::too_many_arguments)]
+fn stft_map(
+    samples: &[f32],
+    window_size: usize,
+    fft_size: usize,
+    hop_size: usize,
+    num_freq_bins: usize,
+    gate_db: Option<f32>,
+    progress: Option<&ProgressCallback>,
+    value: impl Fn(&Complex<f32>) -> f32 + Sync,
+) -> Result<(Vec<Vec<f32>>, usize)> {
+    let num_time_frames = frame_count(samples.len(), window_size, hop_size)?;
     
     // Prepare window function (Hann) - pre-computed once
     let window: Vec<f32> = (0..window_size)
         .map(|i| 0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / (window_size as f32 - 1.0)).cos()))
         .collect();
 
-    // Pre-compute FFT plan once and share across threads
-    let mut planner = FftPlanner::new();
-    let fft = Arc::new(planner.plan_fft_forward(window_size));
+    // Gate on windowed energy: sum((s*w)^2) below gate_power * sum(w^2) means RMS below the gate
+    let window_power: f32 = window.iter().map(|w| w * w).sum();
+    let gate_energy = gate_db.map(|db| 10f32.powf(db / 10.0) * window_power);
+    let gated = AtomicUsize::new(0);
 
-    // Setup progress bar
-    let pb = if quiet {
-        ProgressBar::hidden()
-    } else {
-        let pb = ProgressBar::new(num_time_frames as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} {msg} [{bar:40.cyan/blue}] {pos}/{len} frames ({percent}%)")
-                .unwrap()
-                .progress_chars("━━╸")
-        );
-        pb.set_message("STFT");
-        pb
-    };
+    // Reuse the FFT plan for this size (shared across threads and files)
+    let fft = cached_fft(fft_size);
+
+    let frames_done = AtomicUsize::new(0);
 
     // Process frames in parallel with shared FFT plan
-    let magnitudes: Vec<Vec<f32>> = (0..num_time_frames)
+    let values: Vec<Vec<f32>> = (0..num_time_frames)
         .into_par_iter()
         .map(|frame_idx| {
+            // Skip the remaining frames once cancelled; the result is discarded below
+            if progress::is_cancelled() {
+                return Vec::new();
+            }
+
             let start = frame_idx * hop_size;
             let end = start + window_size;
             
@@ -102,246 +475,841 @@ fn compute_stft(samples: &[f32], window_size: usize, hop_size: usize, quiet: boo
                 .zip(window.iter())
                 .map(|(&s, &w)| Complex { re: s * w, im: 0.0 })
                 .collect();
+
+            let below_gate = gate_energy.is_some_and(|gate| {
+                buffer.iter().map(|c| c.re * c.re).sum::<f32>() < gate
+            });
+            buffer.resize(fft_size, Complex { re: 0.0, im: 0.0 });
+            let frame_values: Vec<f32> = if below_gate {
+                gated.fetch_add(1, Ordering::Relaxed);
+                vec![0.0; num_freq_bins]
+            } else {
+                // Compute FFT
+                fft.process(&mut buffer);
+
+                // Extract values (at most the first half, up to Nyquist)
+                buffer[0..num_freq_bins]
+                    .iter()
+                    .map(&value)
+                    .collect()
+            };
             
-            // Compute FFT
-            fft.process(&mut buffer);
-            
-            // Extract magnitudes (only first half, up to Nyquist)
-            let frame_mags: Vec<f32> = buffer[0..num_freq_bins]
-                .iter()
-                .map(|c| c.norm())
-                .collect();
-            
-            // Update progress (approximate in parallel)
-            if frame_idx % 50 == 0 {
-                pb.inc(50);
+            // Update progress every 50 frames
+            let done = frames_done.fetch_add(1, Ordering::Relaxed) + 1;
+            if done.is_multiple_of(50) {
+                progress::report(progress, Stage::Stft, done as f32 / num_time_frames as f32);
             }
             
-            frame_mags
+            frame_values
         })
         .collect();
 
-    if !quiet {
-        pb.finish_with_message("STFT ✓");
+    progress::check_cancelled()?;
+    progress::report(progress, Stage::Stft, 1.0);
+
+    Ok((values, gated.into_inner()))
+}
+
+/// Low-frequency magnitudes from a `BASS_WINDOW_FACTOR` times longer FFT, realigned to the regular STFT:
+/// one frame per regular frame (same center time, clamped at the ends), scaled so a sine
+/// reads the same level in both
+struct BassBand {
+    frames: Vec<Vec<f32>>,
+    bin_hz: f32,
+}
+
+impl BassBand {
+    fn compute(
+        samples: &[f32],
+        sample_rate: u32,
+        window_size: usize,
+        stft: &StftResult,
+        dc_bins: usize,
+        gate_db: Option<f32>,
+    ) -> Result<Option<Self>> {
+        let bass_window_size = window_size * BASS_WINDOW_FACTOR;
+        let hop = hop_size(window_size);
+        if samples.len() < bass_window_size {
+            return Ok(None);
+        }
+
+        let bin_hz = sample_rate as f32 / bass_window_size as f32;
+        let num_bins = ((2.0 * BASS_CROSSOVER / bin_hz).ceil() as usize + 2).min(bass_window_size / 2);
+        let mut bass = stft_lowest_bins(samples, bass_window_size, bass_window_size, hop, num_bins, gate_db, None)?;
+        // Zero the same span of spectrum as the regular DC removal
+        remove_dc(&mut bass, dc_bins * BASS_WINDOW_FACTOR);
+
+        // Match the regular STFT's full-scale level (fft_bins / 2, which zero-padding raises)
+        let scale = 2.0 * stft.fft_bins as f32 / bass_window_size as f32;
+        let offset = (bass_window_size - window_size) / 2 / hop;
+        let frames = (0..stft.num_time_frames)
+            .into_par_iter()
+            .map(|t| {
+                let source = t.saturating_sub(offset).min(bass.num_time_frames - 1);
+                bass.magnitudes[source].iter().map(|&m| m * scale).collect()
+            })
+            .collect();
+
+        Ok(Some(BassBand { frames, bin_hz }))
     }
+}
 
-    Ok(StftResult {
-        magnitudes,
-        num_time_frames,
-        num_freq_bins,
-    })
+/// Bins on each side of a bin compared against when sharpening
+const SHARPEN_RADIUS: usize = 3;
+
+/// Narrow bright ridges along the frequency axis: each bin is scaled by
+/// `(mag / local_max) ^ strength`, where `local_max` is the loudest bin within
+/// `SHARPEN_RADIUS`. Local peaks keep their level while their window-leakage
+/// skirts fall off, so tonal components read as thin lines.
+pub fn sharpen_ridges(frames: &[Vec<f32>], strength: f32) -> Vec<Vec<f32>> {
+    frames.par_iter()
+        .map(|frame| {
+            (0..frame.len())
+                .map(|bin| {
+                    let mag = frame[bin];
+                    let lo = bin.saturating_sub(SHARPEN_RADIUS);
+                    let hi = (bin + SHARPEN_RADIUS + 1).min(frame.len());
+                    let local_max = frame[lo..hi].iter().fold(0.0f32, |max, &m| max.max(m));
+                    if local_max > 0.0 { mag * (mag / local_max).powf(strength) } else { 0.0 }
+                })
+                .collect()
+        })
+        .collect()
 }
 
-/// Compute spectral rolloff for each time frame
-/// Rolloff is the frequency below which 85% of the total energy is contained
-fn compute_spectral_rolloff(stft: &StftResult, sample_rate: u32, output_width: u32) -> Vec<f32> {
-    let nyquist = sample_rate as f32 / 2.0;
-    let rolloff_threshold = 0.85; // 85% threshold
-    
-    // Calculate rolloff per time frame
-    let rolloff_per_frame: Vec<f32> = stft.magnitudes.par_iter()
+/// Zero the lowest `bins` bins of every frame. A DC offset lands in bin 0 (and leaks into
+/// bin 1 through the Hann window), where it paints a bright bottom row and skews the rolloff.
+pub fn remove_dc(stft: &mut StftResult, bins: usize) {
+    let bins = bins.min(stft.num_freq_bins);
+    stft.magnitudes.par_iter_mut().for_each(|frame| frame[..bins].fill(0.0));
+}
+
+/// Level of a frame's loudest bin in dBFS (0 dB = full-scale sine), for an FFT of `2 * fft_bins`
+pub fn frame_peak_db(frame: &[f32], fft_bins: usize) -> f32 {
+    let peak = frame.iter().fold(0.0f32, |max, &val| max.max(val));
+    20.0 * (peak / (fft_bins as f32 / 2.0) + 1e-9).log10()
+}
+
+/// Whether a frame falls below the silence threshold
+pub fn is_silent(frame: &[f32], fft_bins: usize, silence_db: f32) -> bool {
+    frame_peak_db(frame, fft_bins) < silence_db
+}
+
+/// Fraction of a frame's energy below the rolloff frequency
+const ROLLOFF_FRACTION: f32 = 0.85;
+
+/// Which per-frame features `compute_frame_stats` gathers; the rest are left at 0.0
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStatsRequest<'a> {
+    /// Frames whose loudest bin is below this level (dBFS) are silent
+    pub silence_db: f32,
+    /// Rolloff with this energy weighting
+    pub rolloff: Option<RolloffMetric>,
+    pub centroid: bool,
+    pub entropy: bool,
+    /// Median bin magnitude, for the noise floor
+    pub median: bool,
+    /// Highest frequency above this level (dBFS)
+    pub cutoff_db: Option<f32>,
+    /// Per-bin display gain; when set, the loudest weighted bin of each frame is recorded
+    pub bin_gain: Option<&'a [f32]>,
+}
+
+impl FrameStatsRequest<'_> {
+    /// Only the peak and silence flag
+    pub fn new(silence_db: f32) -> Self {
+        FrameStatsRequest { silence_db, rolloff: None, centroid: false, entropy: false, median: false, cutoff_db: None, bin_gain: None }
+    }
+}
+
+/// Features of one STFT frame. Features that weren't requested, and every feature except
+/// `peak`, `weighted_peak`, `median` and `cutoff_hz` of a silent frame, are 0.0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Loudest bin (raw magnitude)
+    pub peak: f32,
+    /// Frequency of the loudest bin
+    pub peak_hz: f32,
+    /// Loudest bin after the requested `bin_gain`
+    pub weighted_peak: f32,
+    pub silent: bool,
+    pub rolloff_hz: f32,
+    pub centroid_hz: f32,
+    /// Normalized spectral entropy (see `compute_spectral_entropy`)
+    pub entropy: f32,
+    /// Median bin magnitude (raw), kept for silent frames too
+    pub median: f32,
+    pub cutoff_hz: f32,
+}
+
+/// Gather the requested per-frame features in a single traversal of the STFT, so that
+/// enabling several overlays and metrics doesn't walk the whole matrix once for each.
+/// Only the rolloff and median revisit a frame: to find where its cumulative energy crosses the
+/// threshold, and to select the middle bin.
+pub fn compute_frame_stats(stft: &StftResult, sample_rate: u32, request: &FrameStatsRequest) -> Vec<FrameStats> {
+    let bin_hz = sample_rate as f32 / 2.0 / stft.fft_bins as f32;
+    let norm_factor = stft.fft_bins as f32 / 2.0;
+    // Top of the kept bins: Nyquist unless the spectrum was cut short
+    let max_hz = stft.num_freq_bins as f32 * bin_hz;
+    let max_entropy = (stft.num_freq_bins as f32).ln() as f64;
+    // Compare raw magnitudes against the cutoff threshold instead of converting every bin to dB
+    let cutoff_mag = request.cutoff_db.map(|db| 10f32.powf(db / 20.0) * norm_factor);
+
+    stft.magnitudes.par_iter()
         .map(|frame| {
-            // Sum of squared magnitudes (energy)
-            let total_energy: f32 = frame.iter().map(|m| m * m).sum();
-            
-            if total_energy < 1e-10 {
-                return 0.0;
-            }
-            
-            let threshold_energy = total_energy * rolloff_threshold;
-            let mut cumulative_energy = 0.0;
-            
+            let mut peak = 0.0f32;
+            let mut peak_bin = 0;
+            let mut weighted_peak = 0.0f32;
+            let mut total_power = 0.0f32;
+            let mut total_magnitude = 0.0f32;
+            let mut weighted_bins = 0.0f32;
+            // Entropy as ln(P) - sum(p ln p) / P, accumulated in f64 to avoid cancellation
+            let mut power_log_power = 0.0f64;
+            let mut cutoff_bin = None;
+
             for (bin, &mag) in frame.iter().enumerate() {
-                cumulative_energy += mag * mag;
-                if cumulative_energy >= threshold_energy {
-                    // Convert bin to frequency
-                    let freq = (bin as f32 / stft.num_freq_bins as f32) * nyquist;
-                    return freq;
+                if mag > peak {
+                    peak = mag;
+                    peak_bin = bin;
+                }
+                if let Some(gain) = request.bin_gain {
+                    weighted_peak = weighted_peak.max(mag * gain[bin]);
+                }
+                let power = mag * mag;
+                total_power += power;
+                total_magnitude += mag;
+                weighted_bins += bin as f32 * mag;
+                if request.entropy && power > 0.0 {
+                    power_log_power += power as f64 * (power as f64).ln();
+                }
+                if cutoff_mag.is_some_and(|threshold| mag > threshold) {
+                    cutoff_bin = Some(bin);
                 }
             }
-            
-            nyquist // All energy used, rolloff at max
+
+            let mut stats = FrameStats {
+                peak,
+                weighted_peak,
+                silent: 20.0 * (peak / norm_factor + 1e-9).log10() < request.silence_db,
+                cutoff_hz: cutoff_bin.map_or(0.0, |bin| bin as f32 * bin_hz),
+                ..FrameStats::default()
+            };
+            if request.median {
+                let mut sorted = frame.clone();
+                let middle = sorted.len() / 2;
+                stats.median = *sorted.select_nth_unstable_by(middle, |a, b| a.total_cmp(b)).1;
+            }
+            if stats.silent {
+                return stats;
+            }
+            stats.peak_hz = peak_bin as f32 * bin_hz;
+
+            if let Some(metric) = request.rolloff {
+                let energy = |mag: f32| match metric {
+                    RolloffMetric::Power => mag * mag,
+                    RolloffMetric::Magnitude => mag,
+                };
+                let total = match metric {
+                    RolloffMetric::Power => total_power,
+                    RolloffMetric::Magnitude => total_magnitude,
+                };
+                let threshold = total * ROLLOFF_FRACTION;
+                let mut cumulative = 0.0;
+                // All energy used without crossing: rolloff at max
+                stats.rolloff_hz = max_hz;
+                for (bin, &mag) in frame.iter().enumerate() {
+                    cumulative += energy(mag);
+                    if cumulative >= threshold {
+                        stats.rolloff_hz = bin as f32 * bin_hz;
+                        break;
+                    }
+                }
+            }
+            if request.centroid {
+                stats.centroid_hz = weighted_bins * bin_hz / total_magnitude;
+            }
+            if request.entropy {
+                let total = total_power as f64;
+                let entropy = total.ln() - power_log_power / total;
+                stats.entropy = (entropy / max_entropy).clamp(0.0, 1.0) as f32;
+            }
+            stats
         })
-        .collect();
-    
-    // Resample to match output width (one value per pixel column)
-    let num_frames = rolloff_per_frame.len();
+        .collect()
+}
+
+/// Compute spectral rolloff for each time frame
+/// Rolloff is the frequency below which 85% of the total energy is contained,
+/// with energy taken as power (squared magnitude) or plain magnitude per `metric`.
+/// Silent frames yield 0.0. The trace is smoothed over `smooth_frames` frames (see `smooth_frames`).
+pub fn compute_spectral_rolloff(
+    stft: &StftResult,
+    sample_rate: u32,
+    output_width: u32,
+    silence_db: f32,
+    metric: RolloffMetric,
+    smooth_frames: usize,
+) -> Vec<f32> {
+    let request = FrameStatsRequest { rolloff: Some(metric), ..FrameStatsRequest::new(silence_db) };
+    let stats = compute_frame_stats(stft, sample_rate, &request);
+    frame_trace(&stats, |s| s.rolloff_hz, smooth_frames, output_width)
+}
+
+/// Compute the spectral centroid (magnitude-weighted mean frequency) for each time frame,
+/// a measure of perceived brightness. Silent frames yield 0.0.
+/// The trace is smoothed over `smooth_frames` frames (see `smooth_frames`).
+pub fn compute_spectral_centroid(
+    stft: &StftResult,
+    sample_rate: u32,
+    output_width: u32,
+    silence_db: f32,
+    smooth_frames: usize,
+) -> Vec<f32> {
+    let request = FrameStatsRequest { centroid: true, ..FrameStatsRequest::new(silence_db) };
+    let stats = compute_frame_stats(stft, sample_rate, &request);
+    frame_trace(&stats, |s| s.centroid_hz, smooth_frames, output_width)
+}
+
+/// One feature of every frame, smoothed over `smooth` frames and resampled to the image width
+fn frame_trace(stats: &[FrameStats], feature: fn(&FrameStats) -> f32, smooth: usize, output_width: u32) -> Vec<f32> {
+    let per_frame: Vec<f32> = stats.iter().map(feature).collect();
+    resample_to_width(&smooth_frames(&per_frame, smooth), output_width)
+}
+
+/// Centered moving average over `frames` frames, shared by the overlay traces.
+/// Silent frames (0.0) stay silent and are left out of their neighbours' averages,
+/// so gaps don't drag the trace down.
+pub fn smooth_frames(per_frame: &[f32], frames: usize) -> Vec<f32> {
+    if frames <= 1 {
+        return per_frame.to_vec();
+    }
+
+    let half = frames / 2;
+    (0..per_frame.len())
+        .map(|i| {
+            if per_frame[i] <= 0.0 {
+                return 0.0;
+            }
+            let window = &per_frame[i.saturating_sub(half)..(i + frames - half).min(per_frame.len())];
+            let (sum, count) = window.iter()
+                .filter(|&&v| v > 0.0)
+                .fold((0.0, 0), |(sum, count), &v| (sum + v, count + 1));
+            sum / count as f32
+        })
+        .collect()
+}
+
+/// Normalized spectral entropy of each non-silent frame: the Shannon entropy of the frame's
+/// power distribution divided by its maximum (`ln(num_freq_bins)`), so 0.0 is a single bin
+/// and 1.0 is flat white noise. Silent frames are skipped.
+pub fn compute_spectral_entropy(stft: &StftResult, silence_db: f32) -> Vec<f32> {
+    // The sample rate only scales frequency features, which aren't requested here
+    let request = FrameStatsRequest { entropy: true, ..FrameStatsRequest::new(silence_db) };
+    frame_entropy(&compute_frame_stats(stft, 0, &request))
+}
+
+/// Noise floor (dBFS, per FFT bin) from frame stats gathered with `median`: a low percentile of
+/// the per-frame median bin levels, so steady tones and loud passages don't raise it. Silent
+/// frames count too, since dither in quiet passages is the floor; digital silence doesn't.
+/// None if every frame is digital silence.
+pub fn noise_floor_db(stats: &[FrameStats], fft_bins: usize) -> Option<f32> {
+    let medians: Vec<f32> = stats.iter().map(|s| s.median).filter(|&m| m > 0.0).collect();
+    if medians.is_empty() {
+        return None;
+    }
+    let floor = percentile(medians, NOISE_FLOOR_PERCENTILE);
+    Some(20.0 * (floor / (fft_bins as f32 / 2.0)).log10())
+}
+
+/// Entropy of the non-silent frames among `stats`
+pub fn frame_entropy(stats: &[FrameStats]) -> Vec<f32> {
+    stats.iter().filter(|s| !s.silent).map(|s| s.entropy).collect()
+}
+
+/// Spectral flux between consecutive frames: the sum of positive per-bin magnitude increases,
+/// in full-scale units (a full-scale sine appearing from silence adds about 1.0).
+/// The result has one entry per frame transition (`num_time_frames - 1`).
+pub fn compute_spectral_flux(stft: &StftResult) -> Vec<f32> {
+    let norm_factor = stft.fft_bins as f32 / 2.0;
+
+    stft.magnitudes.par_windows(2)
+        .map(|pair| {
+            pair[1].iter()
+                .zip(pair[0].iter())
+                .map(|(&cur, &prev)| (cur - prev).max(0.0))
+                .sum::<f32>() / norm_factor
+        })
+        .collect()
+}
+
+/// Resample a per-frame series to one value per output pixel column
+fn resample_to_width(per_frame: &[f32], output_width: u32) -> Vec<f32> {
+    let num_frames = per_frame.len();
     (0..output_width as usize)
         .map(|x| {
             let frame_pos = (x as f32 / output_width as f32) * num_frames as f32;
             let frame_idx = (frame_pos as usize).min(num_frames - 1);
-            rolloff_per_frame[frame_idx]
+            per_frame[frame_idx]
         })
         .collect()
 }
 
+/// Display gain of every kept bin (A-weighting and tilt, or flat)
+fn bin_gains(stft: &StftResult, sample_rate: u32, options: &SpectrogramOptions) -> Vec<f32> {
+    let bin_hz = sample_rate as f32 / 2.0 / stft.fft_bins as f32;
+    (0..stft.num_freq_bins)
+        .map(|bin| display_gain(bin as f32 * bin_hz, options))
+        .collect()
+}
+
+/// Magnitude the top of the color scale is anchored to: the loudest weighted bin, or the
+/// robust percentile of per-frame peaks
+fn reference_magnitude(stft: &StftResult, bin_gain: &[f32], normalize: Normalization) -> f32 {
+    let request = FrameStatsRequest { bin_gain: Some(bin_gain), ..FrameStatsRequest::new(f32::NEG_INFINITY) };
+    // The sample rate only scales frequency features, which aren't requested here
+    reference_from_stats(&compute_frame_stats(stft, 0, &request), normalize)
+}
+
+/// Reference magnitude from per-frame stats gathered with a `bin_gain`
+fn reference_from_stats(stats: &[FrameStats], normalize: Normalization) -> f32 {
+    match normalize {
+        // Absolute normally arrives as a `reference_db` and never gets here; peak is the fallback
+        Normalization::Peak | Normalization::Absolute => stats.iter().fold(0.0f32, |max, s| max.max(s.weighted_peak)),
+        Normalization::Robust => percentile(stats.iter().map(|s| s.weighted_peak).collect(), ROBUST_PERCENTILE),
+    }
+}
+
+/// Level (dBFS) `generate_spectrogram` would put at the top of the color scale for `samples`
+/// without a `reference_db`: one pass of the STFT, DC removal, sharpening and weighting.
+/// The maximum of this over several files is a shared reference that renders them comparably.
+pub fn reference_level_db(samples: &[f32], sample_rate: u32, options: &SpectrogramOptions) -> Result<f32> {
+    let window_size = options.window_size;
+    let fft_size = window_size * options.zero_pad;
+    let samples = &*pad_to_window(samples, window_size);
+    let num_bins = bins_up_to(options.analysis_max_freq, sample_rate, fft_size);
+    let mut stft = stft_lowest_bins(samples, window_size, fft_size, hop_size(window_size), num_bins, options.fft_gate_db, None)?;
+    remove_dc(&mut stft, options.dc_bins * options.zero_pad);
+    if let Some(strength) = options.sharpen {
+        stft.magnitudes = sharpen_ridges(&stft.magnitudes, strength);
+    }
+    let bin_gain = bin_gains(&stft, sample_rate, options);
+    let magnitude = reference_magnitude(&stft, &bin_gain, options.normalize);
+    Ok(20.0 * (magnitude / (stft.fft_bins as f32 / 2.0) + 1e-9).log10())
+}
+
+/// Map the STFT onto a `width` x `height` grid of color scale positions (0.0..=1.0), row by row.
+/// Also returns the level (dBFS) at the top of the color scale.
 fn render_spectrogram(
     stft: &StftResult,
+    bass: Option<&BassBand>,
     sample_rate: u32,
-    width: u32,
-    height: u32,
-    config: &Config,
-    linear: bool,
-    quiet: bool,
-) -> Result<RgbImage> {
-    let mut img = RgbImage::new(width, height);
-    
-    // Pre-compute gradient LUT once
-    let gradient = create_gradient_map(&config.colors.stops, 1024);
-    
-    // Setup progress bar
-    let pb = if quiet {
-        ProgressBar::hidden()
-    } else {
-        let pb = ProgressBar::new(width as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} {msg} [{bar:40.cyan/blue}] {pos}/{len} cols ({percent}%)")
-                .unwrap()
-                .progress_chars("━━╸")
-        );
-        pb.set_message("Rendering");
-        pb
-    };
+    options: &SpectrogramOptions,
+    frame_stats: Option<&[FrameStats]>,
+    progress: Option<&ProgressCallback>,
+) -> Result<(Vec<f32>, f32)> {
+    let (width, height) = (options.width, options.height);
+    let linear = options.linear;
+    let mut levels = vec![0.0f32; width as usize * height as usize];
 
-    // Constants for log scale
-    let min_freq = 20.0; // 20 Hz
-    let max_freq = sample_rate as f32 / 2.0;
+    // Frequency span mapped onto the image height
+    let bin_hz = sample_rate as f32 / 2.0 / stft.fft_bins as f32;
+    let (min_freq, max_freq) = display_range(sample_rate, linear, options.freq_min, options.freq_max);
     
-    // Auto-Normalization (Dynamic Contrast)
-    // Find global peak magnitude first using parallel reduction
-    let global_max_mag = stft.magnitudes.par_iter()
-        .map(|frame| {
-            frame.iter().fold(0.0f32, |max, &val| max.max(val))
-        })
-        .reduce(|| 0.0f32, |a, b| a.max(b));
+    // Per-bin gain applied before color mapping (A-weighting and tilt, or flat)
+    let bin_gain = bin_gains(stft, sample_rate, options);
+
+    // Auto-Normalization (Dynamic Contrast), unless a fixed reference was given
+    let global_max_mag = match (options.reference_db, frame_stats) {
+        (Some(db), _) => 10f32.powf(db / 20.0) * (stft.fft_bins as f32 / 2.0),
+        (None, Some(stats)) => reference_from_stats(stats, options.normalize),
+        (None, None) => reference_magnitude(stft, &bin_gain, options.normalize),
+    };
         
     // Convert max magnitude to dB for reference
-    let max_mag_norm = global_max_mag / (stft.num_freq_bins as f32 / 2.0);
+    let max_mag_norm = global_max_mag / (stft.fft_bins as f32 / 2.0);
     let max_db = 20.0 * (max_mag_norm + 1e-9).log10();
     
-    // Set dynamic range (100dB dynamic range below peak)
-    let min_db = max_db - 100.0;
+    // Set dynamic range below peak
+    let min_db = max_db - DB_RANGE;
     let db_range = max_db - min_db;
     
     // Pre-compute values for inner loop
     let num_time_frames_f = stft.num_time_frames as f32;
-    let num_freq_bins_f = stft.num_freq_bins as f32;
     let height_f = height as f32;
     let width_f = width as f32;
-    let freq_ratio = max_freq / min_freq;
-    let norm_factor = stft.num_freq_bins as f32 / 2.0;
+    let norm_factor = stft.fft_bins as f32 / 2.0;
     
-    // Parallelize column processing
-    let columns: Vec<(u32, Vec<Rgb<u8>>)> = (0..width)
-        .into_par_iter()
+    // With max mapping and more frames than columns, collapse each column's frames into one
+    // (per-bin maximum) so no frame is skipped; columns then map 1:1 without interpolation
+    let column_max = (options.time_mapping == TimeMapping::Max && stft.num_time_frames > width as usize)
+        .then(|| max_per_column(&stft.magnitudes, width));
+    let frames = column_max.as_deref().unwrap_or(&stft.magnitudes);
+    let bass_column_max = bass.filter(|_| column_max.is_some())
+        .map(|bass| max_per_column(&bass.frames, width));
+    let bass_frames = bass.map(|b| bass_column_max.as_deref().unwrap_or(&b.frames));
+
+    // Time interpolation indices depend only on x, so compute them once per column
+    let time_index: Vec<(usize, usize, f32)> = (0..width)
         .map(|x| {
+            if column_max.is_some() {
+                return (x as usize, x as usize, 0.0);
+            }
             // Map pixel x to time frame
             let time_pos = (x as f32 / width_f) * num_time_frames_f;
-            
-            // Pre-compute time interpolation indices
             let t0 = time_pos.floor() as usize;
             let t1 = (t0 + 1).min(stft.num_time_frames - 1);
             let t_fract = time_pos - t0 as f32;
-            let t0 = t0.min(stft.num_time_frames - 1);
-
-            let mut col_pixels = Vec::with_capacity(height as usize);
-
-            for y in 0..height {
-                // y=0 is top (high freq), y=height-1 is bottom (low freq)
-                let y_inverted = height - 1 - y;
-                let y_ratio = y_inverted as f32 / height_f;
-
-                let bin_pos = if linear {
-                    // Linear scale
-                    y_ratio * num_freq_bins_f
-                } else {
-                    // Logarithmic scale
-                    let freq = min_freq * freq_ratio.powf(y_ratio);
-                    (freq / max_freq) * num_freq_bins_f
-                };
+            (t0.min(stft.num_time_frames - 1), t1, t_fract)
+        })
+        .collect();
 
-                // Bilinear Interpolation
-                let f0 = bin_pos.floor() as usize;
-                let f1 = (f0 + 1).min(stft.num_freq_bins - 1);
-                let f_fract = bin_pos - f0 as f32;
-                let f0 = f0.min(stft.num_freq_bins - 1);
-                
+    let rows_done = AtomicUsize::new(0);
+
+    // Fill the level buffer row by row in parallel, writing values in place
+    levels.par_chunks_mut(width as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            if progress::is_cancelled() {
+                return;
+            }
+
+            let freq = row_frequency(y as u32, height, (min_freq, max_freq), linear);
+            let bin_pos = freq / bin_hz;
+
+            // Frequency interpolation indices are shared by the whole row
+            let f0 = bin_pos.floor() as usize;
+            let f1 = (f0 + 1).min(stft.num_freq_bins - 1);
+            let f_fract = bin_pos - f0 as f32;
+            let f0 = f0.min(stft.num_freq_bins - 1);
+
+            // Lowest octaves: crossfade from the regular FFT into the finer bass analysis
+            let bass_row = bass.zip(bass_frames).filter(|_| freq < 2.0 * BASS_CROSSOVER).map(|(band, frames)| {
+                let weight = (2.0 - freq / BASS_CROSSOVER).clamp(0.0, 1.0);
+                let bin_pos = freq / band.bin_hz;
+                let last = band.frames[0].len() - 1;
+                let b0 = (bin_pos.floor() as usize).min(last);
+                let b1 = (b0 + 1).min(last);
+                let gain = display_gain(freq, options);
+                (frames, weight, b0, b1, bin_pos - bin_pos.floor(), gain)
+            });
+
+            for (level, &(t0, t1, t_fract)) in row.iter_mut().zip(time_index.iter()) {
                 // Get 4 samples for bilinear interpolation
-                let m00 = stft.magnitudes[t0][f0];
-                let m01 = stft.magnitudes[t0][f1];
-                let m10 = stft.magnitudes[t1][f0];
-                let m11 = stft.magnitudes[t1][f1];
-                
-                // Interpolate Time
-                let m0 = m00 * (1.0 - t_fract) + m10 * t_fract;
-                let m1 = m01 * (1.0 - t_fract) + m11 * t_fract;
-                
+                let m00 = frames[t0][f0];
+                let m01 = frames[t0][f1];
+                let m10 = frames[t1][f0];
+                let m11 = frames[t1][f1];
+
+                // Interpolate Time, then apply the per-bin gain
+                let m0 = (m00 * (1.0 - t_fract) + m10 * t_fract) * bin_gain[f0];
+                let m1 = (m01 * (1.0 - t_fract) + m11 * t_fract) * bin_gain[f1];
+
                 // Interpolate Freq
-                let mag = m0 * (1.0 - f_fract) + m1 * f_fract;
-            
-                // Convert to dB
-                let normalized_mag = mag / norm_factor;
-                let db = 20.0 * (normalized_mag + 1e-9).log10();
-            
-                // Map dB to color using dynamic range
-                let normalized_val = (db - min_db) / db_range;
-                let clamped = normalized_val.max(0.0).min(1.0);
-            
-                let color_idx = (clamped * 1023.0) as usize;
-                col_pixels.push(gradient[color_idx]);
+                let mut mag = m0 * (1.0 - f_fract) + m1 * f_fract;
+
+                if let Some((bass_frames, weight, b0, b1, b_fract, gain)) = bass_row {
+                    let lerp = |bin: usize| bass_frames[t0][bin] * (1.0 - t_fract) + bass_frames[t1][bin] * t_fract;
+                    let bass_mag = (lerp(b0) * (1.0 - b_fract) + lerp(b1) * b_fract) * gain;
+                    mag = mag * (1.0 - weight) + bass_mag * weight;
+                }
+
+                let normalized_val = match options.amplitude {
+                    AmplitudeScale::Db => {
+                        // Convert to dB
+                        let normalized_mag = mag / norm_factor;
+                        let db = 20.0 * (normalized_mag + 1e-9).log10();
+
+                        // Map dB to color using dynamic range
+                        (db - min_db) / db_range
+                    }
+                    // Map magnitude directly, relative to the global peak
+                    AmplitudeScale::Linear => mag / (global_max_mag + 1e-9),
+                };
+                *level = apply_color_curve(options.color_curve, normalized_val.clamp(0.0, 1.0));
+            }
+
+            let done = rows_done.fetch_add(1, Ordering::Relaxed) + 1;
+            if done.is_multiple_of(64) {
+                progress::report(progress, Stage::Render, done as f32 / height_f);
             }
-             
-            (x, col_pixels)
+        });
+
+    progress::check_cancelled()?;
+    progress::report(progress, Stage::Render, 1.0);
+
+    Ok((levels, max_db))
+}
+
+/// Frequency (Hz) shown by image row `y` (0 = top) when `range` fills the image height
+fn row_frequency(y: u32, height: u32, range: (f32, f32), linear: bool) -> f32 {
+    let (min_freq, max_freq) = range;
+    // y=0 is top (high freq), y=height-1 is bottom (low freq)
+    let y_ratio = (height - 1 - y) as f32 / height as f32;
+    if linear {
+        min_freq + y_ratio * (max_freq - min_freq)
+    } else {
+        min_freq * (max_freq / min_freq).powf(y_ratio)
+    }
+}
+
+/// Color each pixel by the phase of its nearest STFT bin on the (cyclic) `gradient`,
+/// scaled in brightness by the magnitude `levels` so noise below the range stays dark.
+/// Phase can't be interpolated across its wrap, so there is no bilinear smoothing here.
+fn render_phase(
+    phases: &[Vec<f32>],
+    levels: &[f32],
+    gradient: &[Rgb<u8>],
+    sample_rate: u32,
+    options: &SpectrogramOptions,
+) -> RgbImage {
+    let (width, height) = (options.width, options.height);
+    let num_bins = phases[0].len();
+    let nyquist = sample_rate as f32 / 2.0;
+    let range = display_range(sample_rate, options.linear, options.freq_min, options.freq_max);
+    let last_color = (gradient.len() - 1) as f32;
+
+    let rows: Vec<usize> = (0..height)
+        .map(|y| {
+            let bin_pos = row_frequency(y, height, range, options.linear) / nyquist * num_bins as f32;
+            (bin_pos.round() as usize).min(num_bins - 1)
         })
         .collect();
-    
-    // Assemble image
-    for (x, col_pixels) in columns {
-        for (y, pixel) in col_pixels.into_iter().enumerate() {
-            img.put_pixel(x, y as u32, pixel);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let frame = (x as usize * phases.len() / width as usize).min(phases.len() - 1);
+        let phase = phases[frame][rows[y as usize]];
+        let position = (phase + std::f32::consts::PI) / std::f32::consts::TAU;
+        let color = gradient[(position.clamp(0.0, 1.0) * last_color) as usize];
+        let level = levels[(y * width + x) as usize];
+        match options.floor_color {
+            Some(floor) if level == 0.0 => floor,
+            _ => Rgb(color.0.map(|c| (c as f32 * level) as u8)),
         }
-        pb.inc(1);
+    })
+}
+
+/// Render the time-averaged spectrum as a horizontal strip: frequency runs left to right
+/// over the display range and every row is identical. Uses the same dB / linear mapping,
+/// A-weighting and palette as the spectrogram, relative to the loudest averaged bin.
+pub fn render_barcode(
+    average_spectrum: &[f32],
+    sample_rate: u32,
+    config: &Config,
+    options: &SpectrogramOptions,
+) -> RgbImage {
+    let (width, height) = (options.width, options.height);
+    let gradient = create_gradient_map(&config.colors.stops, 1024);
+    let nyquist = sample_rate as f32 / 2.0;
+    let num_bins = average_spectrum.len();
+    let (min_freq, max_freq) = display_range(sample_rate, options.linear, options.freq_min, options.freq_max);
+
+    let gain = |bin: usize| {
+        display_gain(bin as f32 / num_bins as f32 * nyquist, options)
+    };
+    let weighted: Vec<f32> = average_spectrum.iter()
+        .enumerate()
+        .map(|(bin, &mag)| mag * gain(bin))
+        .collect();
+    let max_mag = weighted.iter().fold(0.0f32, |max, &val| max.max(val));
+
+    let column: Vec<Rgb<u8>> = (0..width)
+        .map(|x| {
+            let x_ratio = x as f32 / width as f32;
+            let freq = if options.linear {
+                min_freq + x_ratio * (max_freq - min_freq)
+            } else {
+                min_freq * (max_freq / min_freq).powf(x_ratio)
+            };
+            let bin_pos = (freq / nyquist) * num_bins as f32;
+            let b0 = (bin_pos.floor() as usize).min(num_bins - 1);
+            let b1 = (b0 + 1).min(num_bins - 1);
+            let fract = bin_pos - bin_pos.floor();
+            let mag = weighted[b0] * (1.0 - fract) + weighted[b1] * fract;
+
+            let normalized = match options.amplitude {
+                AmplitudeScale::Db => {
+                    let db = 20.0 * ((mag + 1e-9) / (max_mag + 1e-9)).log10();
+                    (db + DB_RANGE) / DB_RANGE
+                }
+                AmplitudeScale::Linear => mag / (max_mag + 1e-9),
+            };
+            gradient[(apply_color_curve(options.color_curve, normalized.clamp(0.0, 1.0)) * 1023.0) as usize]
+        })
+        .collect();
+
+    RgbImage::from_fn(width, height, |x, _| column[x as usize])
+}
+
+/// Map a color scale position (0.0..=1.0) through `curve`. Every curve keeps 0 and 1 in place.
+pub fn apply_color_curve(curve: ColorCurve, level: f32) -> f32 {
+    match curve {
+        ColorCurve::Linear => level,
+        ColorCurve::Sqrt => level.sqrt(),
+        // log10(1 + 99x) / 2: a tenth of the scale already reaches about half the palette
+        ColorCurve::Log => (1.0 + 99.0 * level).log10() / 2.0,
     }
+}
 
-    if !quiet {
-        pb.finish_with_message("Render ✓");
+/// Per-bin maximum over the frames covered by each of `width` columns.
+/// Requires at least as many frames as columns.
+fn max_per_column(magnitudes: &[Vec<f32>], width: u32) -> Vec<Vec<f32>> {
+    let num_frames = magnitudes.len();
+    (0..width as usize)
+        .into_par_iter()
+        .map(|x| {
+            let start = x * num_frames / width as usize;
+            let end = ((x + 1) * num_frames / width as usize).max(start + 1);
+            let mut column = magnitudes[start].clone();
+            for frame in &magnitudes[start + 1..end] {
+                for (max, &mag) in column.iter_mut().zip(frame.iter()) {
+                    *max = max.max(mag);
+                }
+            }
+            column
+        })
+        .collect()
+}
+
+/// Value at fraction `p` (0..=1) of the sorted values; 0.0 for an empty input
+fn percentile(mut values: Vec<f32>, p: f32) -> f32 {
+    if values.is_empty() {
+        return 0.0;
     }
+    values.par_sort_unstable_by(|a, b| a.total_cmp(b));
+    let index = ((values.len() - 1) as f32 * p).round() as usize;
+    values[index]
+}
 
-    Ok(img)
+/// Display gain at `freq` Hz from the A-weighting and tilt options (1.0 when neither is set)
+fn display_gain(freq: f32, options: &SpectrogramOptions) -> f32 {
+    let a_weight = if options.a_weight { a_weighting_gain(freq) } else { 1.0 };
+    let tilt = options.tilt_db_per_octave.map_or(1.0, |db| tilt_gain(freq, db));
+    a_weight * tilt
 }
 
-fn create_gradient_map(stops: &[ColorStop], size: usize) -> Vec<Rgb<u8>> {
-    let mut map = Vec::with_capacity(size);
-    let mut sorted_stops = stops.to_vec();
-    sorted_stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
-    
-    for i in 0..size {
+/// Linear gain of a `db_per_octave` slope pivoting at `TILT_PIVOT_HZ` (1.0 there).
+/// Frequencies below `LOG_MIN_FREQ` get the gain at `LOG_MIN_FREQ`, so DC stays finite.
+pub fn tilt_gain(freq: f32, db_per_octave: f32) -> f32 {
+    let octaves = (freq.max(LOG_MIN_FREQ) / TILT_PIVOT_HZ).log2();
+    10f32.powf(db_per_octave * octaves / 20.0)
+}
+
+/// Linear gain of the IEC 61672 A-weighting curve at `freq` Hz (1.0 at 1 kHz)
+pub fn a_weighting_gain(freq: f32) -> f32 {
+    let f2 = (freq as f64).powi(2);
+    let r_a = (12194.0f64.powi(2) * f2 * f2)
+        / ((f2 + 20.6f64.powi(2))
+            * ((f2 + 107.7f64.powi(2)) * (f2 + 737.9f64.powi(2))).sqrt()
+            * (f2 + 12194.0f64.powi(2)));
+    // +2.0 dB normalizes the curve to 0 dB at 1 kHz
+    (r_a * 10f64.powf(2.0 / 20.0)) as f32
+}
+
+/// Build a lookup table of `size` colors interpolated between the color stops
+pub fn create_gradient_map(stops: &[ColorStop], size: usize) -> Vec<Rgb<u8>> {
+    gradient_colors(stops, size)
+        .map(|c| Rgb(c.map(|v| v as u8)))
+        .collect()
+}
+
+/// Color `levels` (row-major color scale positions, as kept in `SpectrogramResult::levels`)
+/// with 16 bits per channel, so smooth gradients don't band. Levels of exactly 0 get
+/// `floor_color`, if set, as in the 8-bit image.
+pub fn colorize_levels_16(
+    levels: &[f32],
+    width: u32,
+    height: u32,
+    stops: &[ColorStop],
+    floor_color: Option<Rgb<u8>>,
+) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+    const SIZE: usize = 1 << 16;
+    let gradient: Vec<Rgb<u16>> = gradient_colors(stops, SIZE)
+        .map(|c| Rgb(c.map(|v| (v * 257.0).round() as u16)))
+        .collect();
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let level = levels[(y * width + x) as usize];
+        match floor_color {
+            Some(color) if level == 0.0 => Rgb(color.0.map(|c| c as u16 * 257)),
+            _ => gradient[(level * (SIZE - 1) as f32) as usize],
+        }
+    })
+}
+
+/// Color `levels` like the 8-bit image, but with an 8x8 ordered (Bayer) dither: each channel is
+/// rounded up or down by a per-pixel threshold, so the average color over a smooth area follows
+/// the gradient between two 8-bit steps instead of forming flat bands.
+pub fn colorize_levels_dithered(
+    levels: &[f32],
+    width: u32,
+    height: u32,
+    stops: &[ColorStop],
+    floor_color: Option<Rgb<u8>>,
+) -> RgbImage {
+    const SIZE: usize = 1 << 12;
+    const BAYER: [[u8; 8]; 8] = [
+        [0, 32, 8, 40, 2, 34, 10, 42],
+        [48, 16, 56, 24, 50, 18, 58, 26],
+        [12, 44, 4, 36, 14, 46, 6, 38],
+        [60, 28, 52, 20, 62, 30, 54, 22],
+        [3, 35, 11, 43, 1, 33, 9, 41],
+        [51, 19, 59, 27, 49, 17, 57, 25],
+        [15, 47, 7, 39, 13, 45, 5, 37],
+        [63, 31, 55, 23, 61, 29, 53, 21],
+    ];
+    let gradient: Vec<[f32; 3]> = gradient_colors(stops, SIZE).collect();
+    RgbImage::from_fn(width, height, |x, y| {
+        let level = levels[(y * width + x) as usize];
+        match floor_color {
+            Some(color) if level == 0.0 => color,
+            _ => {
+                let threshold = (BAYER[(y % 8) as usize][(x % 8) as usize] as f32 + 0.5) / 64.0;
+                let color = gradient[(level * (SIZE - 1) as f32) as usize];
+                Rgb(color.map(|v| (v + threshold).floor().min(255.0) as u8))
+            }
+        }
+    })
+}
+
+/// Widest stretch of the color scale (as a fraction of it) that maps to a single 8-bit color.
+/// Palettes with a slow transition between close colors show it as a visible band.
+pub fn widest_color_band(stops: &[ColorStop]) -> f32 {
+    const SIZE: usize = 1 << 12;
+    let mut widest = 0;
+    let mut run = 0;
+    let mut previous = None;
+    for color in create_gradient_map(stops, SIZE) {
+        run = if previous == Some(color) { run + 1 } else { 1 };
+        widest = widest.max(run);
+        previous = Some(color);
+    }
+    widest as f32 / SIZE as f32
+}
+
+/// `size` evenly spaced colors along the stops, as RGB values in 0.0..=255.0
+fn gradient_colors(stops: &[ColorStop], size: usize) -> impl Iterator<Item = [f32; 3]> {
+    let mut sorted_stops: Vec<(f32, [u8; 3])> = stops.iter()
+        .map(|stop| (stop.position, hex_to_rgb(&stop.color)))
+        .collect();
+    sorted_stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    (0..size).map(move |i| {
         let pos = i as f32 / (size - 1) as f32;
         let mut start_stop = &sorted_stops[0];
         let mut end_stop = &sorted_stops[sorted_stops.len() - 1];
         
         for w in sorted_stops.windows(2) {
-            if pos >= w[0].position && pos <= w[1].position {
+            if pos >= w[0].0 && pos <= w[1].0 {
                 start_stop = &w[0];
                 end_stop = &w[1];
                 break;
             }
         }
         
-        let range = end_stop.position - start_stop.position;
-        let t = if range.abs() < f32::EPSILON { 0.0 } else { (pos - start_stop.position) / range };
+        let range = end_stop.0 - start_stop.0;
+        let t = if range.abs() < f32::EPSILON { 0.0 } else { (pos - start_stop.0) / range };
         
-        let start_color = hex_to_rgb(&start_stop.color);
-        let end_color = hex_to_rgb(&end_stop.color);
-        
-        let r = (start_color[0] as f32 * (1.0 - t) + end_color[0] as f32 * t) as u8;
-        let g = (start_color[1] as f32 * (1.0 - t) + end_color[1] as f32 * t) as u8;
-        let b = (start_color[2] as f32 * (1.0 - t) + end_color[2] as f32 * t) as u8;
-        
-        map.push(Rgb([r, g, b]));
-    }
-    map
+        [0, 1, 2].map(|c| start_stop.1[c] as f32 * (1.0 - t) + end_stop.1[c] as f32 * t)
+    })
 }
 
 #[inline(always)]
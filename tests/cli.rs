@@ -0,0 +1,63 @@
+//! Command line parsing, run against the built binary.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Write a short 16-bit mono WAV of a 440 Hz tone and return its path
+fn tone_wav(name: &str) -> PathBuf {
+    const SAMPLE_RATE: u32 = 44_100;
+    let samples: Vec<i16> = (0..SAMPLE_RATE)
+        .map(|i| ((2.0 * std::f32::consts::PI * 440.0 * i as f32 / SAMPLE_RATE as f32).sin() * 8000.0) as i16)
+        .collect();
+    let data_len = samples.len() as u32 * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    samples.iter().for_each(|s| wav.extend_from_slice(&s.to_le_bytes()));
+
+    let path = std::env::temp_dir().join(format!("spek-cli-test-{}-{}.wav", std::process::id(), name));
+    std::fs::write(&path, wav).expect("write test WAV");
+    path
+}
+
+fn spek(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_spek-cli"))
+        .args(args)
+        .output()
+        .expect("run spek-cli")
+}
+
+/// Render `wav` small and quietly with `args` in front, and check that it worked
+fn assert_renders(args: &[&str], wav: &std::path::Path) {
+    let png = wav.with_extension("png");
+    let output = spek(&[args, &[wav.to_str().unwrap(), "-s", png.to_str().unwrap(), "--width", "64", "--height", "64", "--quiet"]].concat());
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    std::fs::remove_file(png).ok();
+}
+
+#[test]
+fn apply_gain_without_a_mode_leaves_the_file_path_alone() {
+    let wav = tone_wav("apply-gain");
+    assert_renders(&["--apply-gain"], &wav);
+    assert_renders(&["--apply-gain=album"], &wav);
+    std::fs::remove_file(wav).ok();
+}
+
+#[test]
+fn loudness_normalize_without_a_target_leaves_the_file_path_alone() {
+    let wav = tone_wav("loudness-normalize");
+    assert_renders(&["--loudness-normalize"], &wav);
+    assert_renders(&["--loudness-normalize=-16"], &wav);
+    std::fs::remove_file(wav).ok();
+}
@@ -0,0 +1,489 @@
+//! Regression tests for the numeric pipeline, run on synthetic signals generated in memory.
+
+use spek_cli::analysis::{self, Verdict};
+use spek_cli::config::ColorStop;
+use spek_cli::report;
+use spek_cli::{ColorCurve, RolloffMetric};
+use spek_cli::spectrogram::{
+    apply_color_curve, bins_up_to, colorize_levels_dithered, compute_frame_stats, compute_spectral_entropy, compute_spectral_flux, compute_spectral_rolloff, compute_stft, compute_stft_gated, compute_stft_padded, frame_count, noise_floor_db, pad_to_window, remove_dc, smooth_frames,
+    stft_lowest_bins, tilt_gain, widest_color_band, FeatureSeries, FrameStatsRequest, StftResult,
+    hop_size, HOP_SIZE, WINDOW_SIZE,
+};
+
+const SAMPLE_RATE: u32 = 44_100;
+const SILENCE_DB: f32 = -60.0;
+
+/// Sum of equal-amplitude sines at `freqs` (Hz)
+fn tones(freqs: &[f32], secs: f32) -> Vec<f32> {
+    let len = (secs * SAMPLE_RATE as f32) as usize;
+    (0..len)
+        .map(|i| {
+            // Phase in f64: f32 rounding of large phases adds broadband noise
+            let t = i as f64 / SAMPLE_RATE as f64;
+            freqs.iter()
+                .map(|&f| 0.4 * (2.0 * std::f64::consts::PI * f as f64 * t).sin())
+                .sum::<f64>() as f32
+        })
+        .collect()
+}
+
+/// Deterministic white noise from a 32-bit LCG, uniform in `-amplitude / 2..amplitude / 2`
+fn white_noise(len: usize, amplitude: f32) -> Vec<f32> {
+    let mut state = 1u32;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            ((state >> 8) as f32 / (1 << 24) as f32 - 0.5) * amplitude
+        })
+        .collect()
+}
+
+fn stft(samples: &[f32]) -> StftResult {
+    compute_stft(samples, WINDOW_SIZE, HOP_SIZE, None).unwrap()
+}
+
+fn bin_of(freq: f32, stft: &StftResult) -> usize {
+    (freq / (SAMPLE_RATE as f32 / 2.0) * stft.num_freq_bins as f32).round() as usize
+}
+
+fn bin_width_hz(stft: &StftResult) -> f32 {
+    SAMPLE_RATE as f32 / 2.0 / stft.num_freq_bins as f32
+}
+
+#[test]
+fn stft_dimensions_follow_window_and_hop() {
+    let samples = tones(&[1000.0], 1.0);
+    let result = stft(&samples);
+
+    assert_eq!(result.num_freq_bins, WINDOW_SIZE / 2);
+    assert_eq!(result.num_time_frames, (samples.len() - WINDOW_SIZE) / HOP_SIZE + 1);
+    assert_eq!(result.magnitudes.len(), result.num_time_frames);
+    assert!(result.magnitudes.iter().all(|frame| frame.len() == result.num_freq_bins));
+}
+
+#[test]
+fn degenerate_stft_parameters_are_rejected() {
+    assert_eq!(frame_count(WINDOW_SIZE, WINDOW_SIZE, HOP_SIZE).unwrap(), 1);
+    assert!(frame_count(WINDOW_SIZE - 1, WINDOW_SIZE, HOP_SIZE).is_err());
+    assert!(frame_count(WINDOW_SIZE, WINDOW_SIZE, 0).is_err());
+    assert!(frame_count(WINDOW_SIZE, 1, 1).is_err());
+    assert!(compute_stft(&[0.0; 100], WINDOW_SIZE, HOP_SIZE, None).is_err());
+}
+
+#[test]
+fn clips_shorter_than_a_window_are_padded_to_one_frame() {
+    let clip = tones(&[3000.0], 0.02);
+    assert!(clip.len() < WINDOW_SIZE);
+    let padded = pad_to_window(&clip, WINDOW_SIZE);
+    assert_eq!(padded.len(), WINDOW_SIZE);
+    assert!(pad_to_window(&[], WINDOW_SIZE).is_empty());
+
+    let result = stft(&padded);
+    assert_eq!(result.num_time_frames, 1);
+    let peak = (0..result.num_freq_bins)
+        .max_by(|&a, &b| result.magnitudes[0][a].total_cmp(&result.magnitudes[0][b]))
+        .unwrap();
+    assert!(peak.abs_diff(bin_of(3000.0, &result)) <= 1, "peak in bin {}", peak);
+}
+
+#[test]
+fn dropping_high_bins_keeps_low_frequencies_and_levels() {
+    let samples = tones(&[1000.0, 15000.0], 1.0);
+    let full = stft(&samples);
+    assert_eq!(bins_up_to(None, SAMPLE_RATE, WINDOW_SIZE), WINDOW_SIZE / 2);
+    assert_eq!(bins_up_to(Some(96_000.0), SAMPLE_RATE, WINDOW_SIZE), WINDOW_SIZE / 2);
+
+    let num_bins = bins_up_to(Some(5000.0), SAMPLE_RATE, WINDOW_SIZE);
+    let low = stft_lowest_bins(&samples, WINDOW_SIZE, WINDOW_SIZE, HOP_SIZE, num_bins, None, None).unwrap();
+    assert_eq!((low.num_freq_bins, low.fft_bins), (num_bins, full.num_freq_bins));
+    assert!((num_bins - 1) as f32 * bin_width_hz(&full) >= 5000.0);
+    for (kept, all) in low.magnitudes.iter().zip(&full.magnitudes) {
+        assert_eq!(kept[..], all[..num_bins]);
+    }
+
+    // The averaged spectrum still spans Nyquist: the 1 kHz tone keeps its frequency and level,
+    // the 15 kHz one is gone
+    let average = analysis::average_spectrum(&low, SILENCE_DB);
+    assert_eq!(average.len(), full.num_freq_bins);
+    let peaks = analysis::detect_tonal_peaks(&average, SAMPLE_RATE, 4);
+    assert_eq!(peaks.len(), 1, "peaks {:?}", peaks);
+    assert!((peaks[0].freq_hz - 1000.0).abs() < 0.25 * bin_width_hz(&full), "peaks {:?}", peaks);
+    assert!((peaks[0].level_db + 7.96).abs() < 1.5, "level {} dB", peaks[0].level_db);
+}
+
+#[test]
+fn energy_gate_skips_only_silent_frames() {
+    let mut samples = vec![0.0; SAMPLE_RATE as usize / 2];
+    samples.extend(tones(&[1000.0], 0.5));
+    let plain = stft(&samples);
+    let gated = compute_stft_gated(&samples, WINDOW_SIZE, HOP_SIZE, Some(-120.0), None).unwrap();
+
+    // Frames fully inside the silent half are gated; any frame touching the tone is not
+    let silent_frames = (samples.len() / 2 - WINDOW_SIZE) / HOP_SIZE + 1;
+    assert_eq!(plain.gated_frames, 0);
+    assert_eq!(gated.gated_frames, silent_frames);
+    assert_eq!(gated.magnitudes, plain.magnitudes);
+}
+
+#[test]
+fn non_power_of_two_fft_aligns_bins_to_exact_hz() {
+    // 4410 samples at 44.1 kHz: 10 Hz bins, so a 1 kHz tone sits exactly on bin 100
+    let window_size = 4410;
+    let result = compute_stft(&tones(&[1000.0], 1.0), window_size, hop_size(window_size), None).unwrap();
+    assert_eq!(result.num_freq_bins, 2205);
+    assert_eq!(result.num_time_frames, (SAMPLE_RATE as usize - window_size) / hop_size(window_size) + 1);
+
+    let frame = &result.magnitudes[result.num_time_frames / 2];
+    let peak = (0..frame.len()).max_by(|&a, &b| frame[a].total_cmp(&frame[b])).unwrap();
+    assert_eq!(peak, 100);
+    // Equal neighbours: the tone sits exactly on the bin center
+    assert!((frame[99] - frame[101]).abs() < 1e-3 * frame[100], "{} vs {}", frame[99], frame[101]);
+}
+
+#[test]
+fn zero_padding_interpolates_bins_at_the_same_level() {
+    // 10 Hz bins padded to 5 Hz: the 1 kHz tone moves to bin 200 with its level unchanged
+    let window_size = 4410;
+    let samples = tones(&[1000.0], 1.0);
+    let plain = compute_stft(&samples, window_size, hop_size(window_size), None).unwrap();
+    let padded = compute_stft_padded(&samples, window_size, 2 * window_size, hop_size(window_size), None, None).unwrap();
+    assert_eq!(padded.num_freq_bins, 2 * plain.num_freq_bins);
+    assert_eq!(padded.num_time_frames, plain.num_time_frames);
+
+    let frame = &padded.magnitudes[padded.num_time_frames / 2];
+    let peak = (0..frame.len()).max_by(|&a, &b| frame[a].total_cmp(&frame[b])).unwrap();
+    assert_eq!(peak, 200);
+    let level = |mag: f32, stft: &StftResult| mag / (stft.num_freq_bins as f32 / 2.0);
+    let plain_level = level(plain.magnitudes[plain.num_time_frames / 2][100], &plain);
+    assert!((level(frame[200], &padded) - plain_level).abs() < 1e-4, "{} vs {}", level(frame[200], &padded), plain_level);
+}
+
+#[test]
+fn highest_frequency_reports_the_bin_center() {
+    // 10 Hz bins: a 1 kHz tone is bin 100 and nothing above it is within 3 dB
+    let window_size = 4410;
+    let result = compute_stft(&tones(&[1000.0], 1.0), window_size, hop_size(window_size), None).unwrap();
+    let average = analysis::average_spectrum(&result, SILENCE_DB);
+    assert_eq!(analysis::highest_frequency_above(&average, SAMPLE_RATE, -3.0), Some(1000.0));
+}
+
+#[test]
+fn stft_peaks_land_in_tone_bins() {
+    let result = stft(&tones(&[1000.0, 15000.0], 1.0));
+    let frame = &result.magnitudes[result.num_time_frames / 2];
+
+    // The two strongest local maxima are the two tones
+    let mut peaks: Vec<(usize, f32)> = (1..frame.len() - 1)
+        .filter(|&b| frame[b] > frame[b - 1] && frame[b] >= frame[b + 1])
+        .map(|b| (b, frame[b]))
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut top: Vec<usize> = peaks.iter().take(2).map(|&(b, _)| b).collect();
+    top.sort();
+
+    assert!(top[0].abs_diff(bin_of(1000.0, &result)) <= 1, "peak bins {:?}", top);
+    assert!(top[1].abs_diff(bin_of(15000.0, &result)) <= 1, "peak bins {:?}", top);
+}
+
+#[test]
+fn reversed_audio_gives_mirrored_frames() {
+    // Frames tile the signal exactly, so reversing it maps frame t onto frame n - 1 - t;
+    // the Hann window is symmetric, so magnitudes match
+    let len = WINDOW_SIZE + 40 * HOP_SIZE;
+    let mut samples = tones(&[440.0], 1.0)[..len].to_vec();
+    samples[len / 3..].iter_mut().zip(tones(&[3000.0], 1.0)).for_each(|(s, t)| *s += t);
+    let forward = stft(&samples);
+    samples.reverse();
+    let reversed = stft(&samples);
+
+    let n = forward.num_time_frames;
+    assert_eq!(reversed.num_time_frames, n);
+    for t in 0..n {
+        for (a, b) in forward.magnitudes[t].iter().zip(&reversed.magnitudes[n - 1 - t]) {
+            assert!((a - b).abs() <= 1e-3 * a.max(1.0), "frame {}: {} vs {}", t, a, b);
+        }
+    }
+}
+
+#[test]
+fn rolloff_tracks_the_highest_tone() {
+    // Equal energy in both tones puts the 85% point at the upper one
+    let result = stft(&tones(&[1000.0, 15000.0], 1.0));
+    let rolloff = compute_spectral_rolloff(&result, SAMPLE_RATE, 64, SILENCE_DB, RolloffMetric::Power, 1);
+
+    assert_eq!(rolloff.len(), 64);
+    let tolerance = 2.0 * bin_width_hz(&result);
+    for freq in rolloff {
+        assert!((freq - 15000.0).abs() <= tolerance, "rolloff {} Hz", freq);
+    }
+}
+
+#[test]
+fn frame_peak_follows_the_louder_tone() {
+    let mut samples = tones(&[1000.0], 1.0);
+    for (s, t) in samples.iter_mut().zip(tones(&[6000.0], 1.0)) {
+        *s = 0.25 * *s + t;
+    }
+    let result = stft(&samples);
+    let stats = compute_frame_stats(&result, SAMPLE_RATE, &FrameStatsRequest::new(SILENCE_DB));
+
+    let tolerance = bin_width_hz(&result);
+    for frame in stats {
+        assert!((frame.peak_hz - 6000.0).abs() <= tolerance, "peak {} Hz", frame.peak_hz);
+    }
+}
+
+#[test]
+fn magnitude_rolloff_sits_above_power_rolloff() {
+    // Leakage skirts weigh more in magnitude sums, so the 85% point moves up
+    let result = stft(&tones(&[1000.0], 1.0));
+    let power = compute_spectral_rolloff(&result, SAMPLE_RATE, 16, SILENCE_DB, RolloffMetric::Power, 1);
+    let magnitude = compute_spectral_rolloff(&result, SAMPLE_RATE, 16, SILENCE_DB, RolloffMetric::Magnitude, 1);
+
+    for (p, m) in power.iter().zip(magnitude.iter()) {
+        assert!(m >= p, "magnitude rolloff {} Hz below power rolloff {} Hz", m, p);
+    }
+}
+
+#[test]
+fn rolloff_is_zero_for_silence() {
+    let result = stft(&vec![0.0; SAMPLE_RATE as usize]);
+    let rolloff = compute_spectral_rolloff(&result, SAMPLE_RATE, 32, SILENCE_DB, RolloffMetric::Power, 1);
+
+    assert!(rolloff.iter().all(|&f| f == 0.0));
+}
+
+#[test]
+fn removing_dc_bins_restores_rolloff() {
+    // A large DC offset dominates the energy until bin 0 and its window leakage are dropped
+    let samples: Vec<f32> = tones(&[2000.0], 1.0).iter().map(|s| s + 0.9).collect();
+    let mut result = stft(&samples);
+    let with_dc = compute_spectral_rolloff(&result, SAMPLE_RATE, 8, SILENCE_DB, RolloffMetric::Power, 1);
+    assert!(with_dc.iter().all(|&f| f < 100.0), "rolloff {:?}", with_dc);
+
+    remove_dc(&mut result, 2);
+    assert!(result.magnitudes.iter().all(|frame| frame[0] == 0.0 && frame[1] == 0.0));
+    let tolerance = 2.0 * bin_width_hz(&result);
+    for freq in compute_spectral_rolloff(&result, SAMPLE_RATE, 8, SILENCE_DB, RolloffMetric::Power, 1) {
+        assert!((freq - 2000.0).abs() <= tolerance, "rolloff {} Hz", freq);
+    }
+}
+
+#[test]
+fn smoothing_averages_neighbours_and_skips_silence() {
+    let trace = [1000.0, 3000.0, 0.0, 2000.0, 4000.0];
+
+    assert_eq!(smooth_frames(&trace, 1), trace.to_vec());
+    assert_eq!(smooth_frames(&trace, 3), vec![2000.0, 2000.0, 0.0, 3000.0, 3000.0]);
+}
+
+#[test]
+fn spectral_flux_peaks_at_onset() {
+    // Silence, then a tone starting halfway
+    let mut samples = vec![0.0; SAMPLE_RATE as usize / 2];
+    samples.extend(tones(&[1000.0], 0.5));
+    let flux = compute_spectral_flux(&stft(&samples));
+
+    assert_eq!(flux.len(), stft(&samples).num_time_frames - 1);
+    let (onset, &peak) = flux.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .unwrap();
+    let onset_secs = onset as f32 * HOP_SIZE as f32 / SAMPLE_RATE as f32;
+    assert!((onset_secs - 0.5).abs() < 0.06, "onset at {}s", onset_secs);
+    assert!(peak > 100.0 * flux[flux.len() - 1], "steady tail should have near-zero flux");
+}
+
+#[test]
+fn spectral_entropy_separates_tones_from_noise() {
+    let noise = white_noise(SAMPLE_RATE as usize, 1.0);
+    let mean = |e: Vec<f32>| e.iter().sum::<f32>() / e.len() as f32;
+
+    let tone = mean(compute_spectral_entropy(&stft(&tones(&[1000.0], 1.0)), SILENCE_DB));
+    let noise = mean(compute_spectral_entropy(&stft(&noise), SILENCE_DB));
+    assert!(tone < 0.3, "tone entropy {}", tone);
+    assert!(noise > 0.8, "noise entropy {}", noise);
+    assert!(compute_spectral_entropy(&stft(&[0.0; 44_100]), SILENCE_DB).is_empty());
+}
+
+#[test]
+fn noise_floor_ignores_tones_and_digital_silence() {
+    let floor = |samples: &[f32]| {
+        let result = stft(samples);
+        let request = FrameStatsRequest { median: true, ..FrameStatsRequest::new(SILENCE_DB) };
+        noise_floor_db(&compute_frame_stats(&result, SAMPLE_RATE, &request), result.num_freq_bins)
+    };
+
+    let noise = white_noise(SAMPLE_RATE as usize, 1e-3);
+    let with_tones: Vec<f32> = tones(&[1000.0, 5000.0], 1.0).iter().zip(&noise).map(|(t, n)| t + n).collect();
+    let (noise_floor, tone_floor) = (floor(&noise).unwrap(), floor(&with_tones).unwrap());
+    assert!((tone_floor - noise_floor).abs() < 1.0, "{} vs {}", tone_floor, noise_floor);
+    // Uniform noise at 1e-3 peak-to-peak is about -71 dBFS RMS, spread over 1024 bins
+    assert!((-110.0..-90.0).contains(&noise_floor), "{}", noise_floor);
+    assert_eq!(floor(&[0.0; 44_100]), None);
+}
+
+#[test]
+fn cutoff_and_verdict_from_band_limited_signal() {
+    let result = stft(&tones(&[1000.0, 15000.0], 1.0));
+    let spectrum = analysis::analyze_spectrum(&result, SAMPLE_RATE, SILENCE_DB);
+
+    let cutoff = spectrum.cutoff_hz.expect("signal is not silent");
+    assert!(cutoff > 15000.0 && cutoff < 16000.0, "cutoff {} Hz", cutoff);
+    assert_eq!(spectrum.verdict, Verdict::LikelyLossy);
+}
+
+#[test]
+fn tonal_peaks_report_frequency_and_level() {
+    let result = stft(&tones(&[1000.0, 5000.0], 1.0));
+    let average = analysis::average_spectrum(&result, SILENCE_DB);
+    let peaks = analysis::detect_tonal_peaks(&average, SAMPLE_RATE, 4);
+
+    // Only the two tones stand out from the floor
+    assert_eq!(peaks.len(), 2, "peaks {:?}", peaks);
+    let mut freqs: Vec<f32> = peaks.iter().map(|p| p.freq_hz).collect();
+    freqs.sort_by(|a, b| a.total_cmp(b));
+    assert!((freqs[0] - 1000.0).abs() < 0.25 * bin_width_hz(&result), "peaks {:?}", peaks);
+    assert!((freqs[1] - 5000.0).abs() < 0.25 * bin_width_hz(&result), "peaks {:?}", peaks);
+
+    // 0.4 amplitude is -8 dBFS; allow for scalloping between bins
+    for peak in &peaks {
+        assert!((peak.level_db + 7.96).abs() < 1.5, "level {} dB", peak.level_db);
+    }
+}
+
+#[test]
+fn stereo_correlation_tells_mono_from_inverted_and_unrelated() {
+    let left = tones(&[440.0], 0.5);
+    let inverted: Vec<f32> = left.iter().map(|s| -s).collect();
+    let noise = white_noise(left.len(), 0.5);
+
+    let mono = analysis::measure_stereo(&left, &left).unwrap();
+    assert!((mono.correlation - 1.0).abs() < 1e-4 && mono.out_of_phase < 1e-4);
+    let flipped = analysis::measure_stereo(&left, &inverted).unwrap();
+    assert!((flipped.correlation + 1.0).abs() < 1e-4 && flipped.is_mono_incompatible());
+    let unrelated = analysis::measure_stereo(&left, &noise).unwrap();
+    assert!(unrelated.correlation.abs() < 0.05, "correlation {}", unrelated.correlation);
+    assert!((unrelated.out_of_phase - 0.5).abs() < 0.05, "out of phase {}", unrelated.out_of_phase);
+
+    assert!(analysis::measure_stereo(&left, &vec![0.0; left.len()]).is_none());
+}
+
+#[test]
+fn integrated_loudness_matches_the_ebu_reference_tone() {
+    // EBU Tech 3341: a 1 kHz sine at -23 dBFS in both channels of a stereo file is -23 LUFS
+    let gain = 10f32.powf(-23.0 / 20.0) / 0.4;
+    let channel: Vec<f32> = tones(&[1000.0], 5.0).iter().map(|s| s * gain).collect();
+    let stereo = analysis::integrated_loudness(&[channel.clone(), channel.clone()], SAMPLE_RATE).unwrap();
+    assert!((stereo + 23.0).abs() < 0.1, "stereo {} LUFS", stereo);
+
+    // Gating drops the silent half (blocks straddling the edge still count), and a single
+    // channel is 3 dB quieter
+    let mut gapped = channel.clone();
+    gapped.extend(vec![0.0; channel.len()]);
+    let mono = analysis::integrated_loudness(&[gapped], SAMPLE_RATE).unwrap();
+    assert!((mono + 26.0).abs() < 0.2, "mono {} LUFS", mono);
+
+    assert!(analysis::integrated_loudness(&[vec![0.0; SAMPLE_RATE as usize]], SAMPLE_RATE).is_none());
+    assert!(analysis::integrated_loudness(&[channel[..1000].to_vec()], SAMPLE_RATE).is_none());
+}
+
+#[test]
+fn tilt_pivots_at_one_kilohertz() {
+    assert_eq!(tilt_gain(1000.0, 6.0), 1.0);
+    // One octave up at 6 dB/octave is roughly double the magnitude, one down is half
+    assert!((tilt_gain(2000.0, 6.0) - 1.995).abs() < 1e-3);
+    assert!((tilt_gain(500.0, 6.0) - 0.501).abs() < 1e-3);
+    // DC is clamped to the log-scale floor instead of going to zero / infinity
+    assert!(tilt_gain(0.0, -3.0).is_finite());
+}
+
+#[test]
+fn color_curves_keep_the_ends_and_lift_quiet_levels() {
+    for curve in [ColorCurve::Linear, ColorCurve::Sqrt, ColorCurve::Log] {
+        assert_eq!(apply_color_curve(curve, 0.0), 0.0);
+        assert!((apply_color_curve(curve, 1.0) - 1.0).abs() < 1e-6);
+    }
+    let quiet = 0.1;
+    assert_eq!(apply_color_curve(ColorCurve::Linear, quiet), quiet);
+    assert!(apply_color_curve(ColorCurve::Sqrt, quiet) > quiet);
+    assert!(apply_color_curve(ColorCurve::Log, quiet) > apply_color_curve(ColorCurve::Sqrt, quiet));
+}
+
+#[test]
+fn analysis_is_deterministic() {
+    let samples = tones(&[440.0, 3000.0, 12000.0], 1.0);
+
+    let first = stft(&samples);
+    let second = stft(&samples);
+    assert_eq!(first.magnitudes, second.magnitudes);
+
+    assert_eq!(
+        compute_spectral_rolloff(&first, SAMPLE_RATE, 128, SILENCE_DB, RolloffMetric::Power, 1),
+        compute_spectral_rolloff(&second, SAMPLE_RATE, 128, SILENCE_DB, RolloffMetric::Power, 1),
+    );
+}
+
+#[test]
+fn true_peak_finds_intersample_overs() {
+    // A quarter-rate sine offset by 45 degrees is only ever sampled at 0.707 of its amplitude
+    let samples: Vec<f32> = (0..SAMPLE_RATE as usize)
+        .map(|i| (0.4 * (std::f64::consts::FRAC_PI_2 * i as f64 + std::f64::consts::FRAC_PI_4).sin()) as f32)
+        .collect();
+    let peaks = analysis::measure_peaks(&samples);
+
+    assert!((peaks.sample_peak_dbfs + 10.97).abs() < 0.05, "sample peak {} dBFS", peaks.sample_peak_dbfs);
+    assert!((peaks.true_peak_dbtp + 7.96).abs() < 0.2, "true peak {} dBTP", peaks.true_peak_dbtp);
+    assert!(!peaks.is_over());
+
+    // Silence stays finite
+    assert!(analysis::measure_peaks(&[0.0; 64]).true_peak_dbtp.is_finite());
+}
+
+#[test]
+fn dithering_averages_between_color_steps() {
+    // Black to #000004: only five 8-bit blues over the whole scale
+    let stops = [
+        ColorStop { position: 0.0, color: "#000000".to_string() },
+        ColorStop { position: 1.0, color: "#000004".to_string() },
+    ];
+    assert!(widest_color_band(&stops) > 0.2);
+
+    // One full 8x8 dither tile at a level between two steps (blue 1.2)
+    let levels = vec![0.3; 64];
+    let img = colorize_levels_dithered(&levels, 8, 8, &stops, None);
+    let mean = img.pixels().map(|p| p.0[2] as f32).sum::<f32>() / 64.0;
+    assert!((mean - 1.2).abs() < 0.05, "mean blue {}", mean);
+    assert!(img.pixels().all(|p| p.0[2] == 1 || p.0[2] == 2));
+}
+
+#[test]
+fn fingerprint_follows_content_not_level() {
+    let music = tones(&[220.0, 880.0, 3500.0], 2.0);
+    let quieter: Vec<f32> = music.iter().map(|s| s * 0.25).collect();
+    let other = tones(&[330.0, 5000.0], 2.0);
+
+    let hash = analysis::fingerprint(&stft(&music), SAMPLE_RATE);
+    assert_eq!(analysis::fingerprint(&stft(&quieter), SAMPLE_RATE), hash);
+    assert!((analysis::fingerprint(&stft(&other), SAMPLE_RATE) ^ hash).count_ones() > 8);
+}
+
+#[test]
+fn series_csv_reads_back_onto_any_width() {
+    let series = FeatureSeries {
+        rolloff_hz: vec![8000.0, 8500.0, 0.0, 9000.0],
+        centroid_hz: vec![2000.0, 2100.0, 0.0, 2200.0],
+        peak_hz: vec![440.0; 4],
+    };
+    let path = std::env::temp_dir().join(format!("spek-series-{}.csv", std::process::id()));
+    report::write_series_csv(&path, &series, 2.0).unwrap();
+    let overlay = report::SeriesOverlay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Same width: the lines come back as written, silent columns included
+    assert_eq!(overlay.lines(2.0, 4), vec![series.rolloff_hz.clone(), series.centroid_hz.clone()]);
+
+    // Twice the width repeats each row; a longer file leaves the overlay's end empty
+    assert_eq!(overlay.lines(2.0, 8)[0], vec![8000.0, 8000.0, 8500.0, 8500.0, 0.0, 0.0, 9000.0, 9000.0]);
+    assert_eq!(overlay.lines(4.0, 4)[1], vec![2100.0, 2200.0, 0.0, 0.0]);
+}